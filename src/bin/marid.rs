@@ -0,0 +1,249 @@
+//! Runs the processes of a Procfile, like foreman.
+//!
+//! ```text
+//! marid [FILE]
+//! ```
+//!
+//! The file defaults to `Procfile`, with one `name: command` line per process. A file
+//! ending in `.toml` instead lists the processes in a `[processes]` table, as
+//! `name = "command"` keys.
+//!
+//! Every command is run by `sh`, with its stdin closed, and its output is printed
+//! prefixed with its name. On INT or TERM, or once any process fails, every process is
+//! sent TERM, and KILL if it is still running five seconds later. The exit status is
+//! non-zero if any process failed, not counting those stopped by TERM on INT or TERM.
+extern crate chan;
+extern crate libc;
+extern crate marid;
+
+use marid::{launch, CommandRunner, CommandError, Composer, Escalation, GroupError, MaridError,
+            OsSignals, Process, ProcessError, Runner, Sender, Signal, SignalSource};
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{self, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const GRACE_SECS: u64 = 5;
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| "Procfile".to_string());
+    let processes = match read(&path) {
+        Ok(processes) => processes,
+        Err(e) => {
+            let _ = writeln!(io::stderr(), "marid: {}: {}", path, e);
+            process::exit(2);
+        },
+    };
+
+    let width = processes.iter().map(|p| p.0.len()).max().unwrap_or(0);
+    let mut composer = Composer::new(vec!(), Signal::TERM)
+        .escalate(Escalation::new(Signal::TERM, Duration::from_secs(GRACE_SECS),
+                                  Signal::KILL, Duration::from_secs(GRACE_SECS)));
+    for (name, command) in processes {
+        let mut sh = Command::new("sh");
+        sh.arg("-c").arg(command).stdin(Stdio::null());
+        let runner = CommandRunner::new(name.clone(), sh)
+            .prefix(format!("{:1$}", name, width))
+            .output(print);
+        composer = composer.member(&name, Box::new(runner) as Box<Runner + Send>);
+    }
+
+    let stopping = Arc::new(AtomicBool::new(false));
+    let process = launch(composer, Shutdown { stopping: stopping.clone() });
+    match process.wait() {
+        Ok(()) => {},
+        Err(ProcessError::RunnerError(ref e)) if stopping.load(Ordering::SeqCst) && terminated(e) => {},
+        Err(e) => {
+            let _ = writeln!(io::stderr(), "marid: {}", e);
+            process::exit(1);
+        },
+    }
+}
+
+// Delivers both INT and TERM as TERM, the shutdown signal of the Composer, so that they
+// stop the processes the same way, and records that a shutdown was asked for.
+struct Shutdown {
+    stopping: Arc<AtomicBool>,
+}
+
+impl SignalSource for Shutdown {
+    fn start(self: Box<Self>, sender: Sender<Signal>) {
+        let (os_send, os_recv) = chan::sync(16);
+        Box::new(OsSignals::new(vec!(Signal::INT, Signal::TERM))).start(os_send);
+        thread::spawn(move || {
+            for _ in os_recv {
+                self.stopping.store(true, Ordering::SeqCst);
+                sender.send(Signal::TERM);
+            }
+        });
+    }
+}
+
+// Returns whether every process that failed was stopped by the TERM sent on shutdown.
+fn terminated(err: &MaridError) -> bool {
+    let group = match err.downcast_ref::<GroupError>() {
+        Some(group) => group,
+        None => return false,
+    };
+    group.failures().iter().all(|f| match f.error.downcast_ref::<CommandError>() {
+        Some(&CommandError::Exited(status)) => by_term(status),
+        _ => false,
+    })
+}
+
+// Shells exit with 128 plus the number of the signal that stopped their command.
+fn by_term(status: ExitStatus) -> bool {
+    status.signal() == Some(libc::SIGTERM) || status.code() == Some(128 + libc::SIGTERM)
+}
+
+// Prints a line of output, without interleaving it with the lines of other processes.
+fn print(line: &str) {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let _ = writeln!(out, "{}", line);
+}
+
+fn read(path: &str) -> Result<Vec<(String, String)>, String> {
+    let mut contents = String::new();
+    try!(File::open(path).and_then(|mut f| f.read_to_string(&mut contents)).map_err(|e| e.to_string()));
+    let processes = if path.ends_with(".toml") {
+        try!(parse_toml(&contents))
+    } else {
+        try!(parse_procfile(&contents))
+    };
+
+    if processes.is_empty() {
+        return Err("no processes".to_string())
+    }
+    for (i, p) in processes.iter().enumerate() {
+        if processes[..i].iter().any(|q| q.0 == p.0) {
+            return Err(format!("duplicate process name: {}", p.0))
+        }
+    }
+    Ok(processes)
+}
+
+// Parses `name: command` lines, skipping blank lines and comments.
+fn parse_procfile(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut processes = vec!();
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        let (name, command) = match line.find(':') {
+            Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+            None => return Err(format!("line {}: expected `name: command`", n + 1)),
+        };
+        if !valid_name(name) || command.is_empty() {
+            return Err(format!("line {}: expected `name: command`", n + 1))
+        }
+        processes.push((name.to_string(), command.to_string()));
+    }
+    Ok(processes)
+}
+
+// Parses the `name = "command"` keys of the `[processes]` table. Only the basic and
+// literal strings of TOML are supported as values.
+fn parse_toml(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut processes = vec!();
+    let mut in_processes = false;
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        if line.starts_with('[') {
+            in_processes = line == "[processes]";
+            continue
+        }
+        if !in_processes {
+            continue
+        }
+        let err = || format!("line {}: expected `name = \"command\"`", n + 1);
+        let (name, value) = match line.find('=') {
+            Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+            None => return Err(err()),
+        };
+        let command = try!(toml_string(value).ok_or_else(&err));
+        if !valid_name(name) || command.is_empty() {
+            return Err(err())
+        }
+        processes.push((name.to_string(), command));
+    }
+    Ok(processes)
+}
+
+// Parses a TOML basic or literal string, followed by an optional comment.
+fn toml_string(value: &str) -> Option<String> {
+    let mut chars = value.chars();
+    let quote = match chars.next() {
+        Some(q) if q == '"' || q == '\'' => q,
+        _ => return None,
+    };
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            None => return None,
+            Some(c) if c == quote => break,
+            Some('\\') if quote == '"' => match chars.next() {
+                Some('"') => s.push('"'),
+                Some('\\') => s.push('\\'),
+                Some('n') => s.push('\n'),
+                Some('t') => s.push('\t'),
+                _ => return None,
+            },
+            Some(c) => s.push(c),
+        }
+    }
+    let rest = chars.as_str().trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Some(s)
+    } else {
+        None
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_procfile, parse_toml, by_term};
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+
+    #[test]
+    fn test_parse_procfile() {
+        let procfile = "# Services\nweb: python -m http.server 8000\n\nworker:  ./worker --queue a:b\n";
+        assert_eq!(parse_procfile(procfile).unwrap(),
+                   vec!(("web".to_string(), "python -m http.server 8000".to_string()),
+                        ("worker".to_string(), "./worker --queue a:b".to_string())));
+        assert_eq!(parse_procfile("web python\n").unwrap_err(), "line 1: expected `name: command`");
+        assert!(parse_procfile("web:\n").is_err());
+    }
+
+    #[test]
+    fn test_by_term() {
+        assert!(by_term(ExitStatus::from_raw(15)));
+        assert!(by_term(ExitStatus::from_raw(143 << 8)));
+        assert!(!by_term(ExitStatus::from_raw(9)));
+        assert!(!by_term(ExitStatus::from_raw(1 << 8)));
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let toml = "[settings]\nweb = \"ignored\"\n\n[processes]\nweb = \"echo \\\"hi\\\"\" # greet\nworker = './worker'\n";
+        assert_eq!(parse_toml(toml).unwrap(),
+                   vec!(("web".to_string(), "echo \"hi\"".to_string()),
+                        ("worker".to_string(), "./worker".to_string())));
+        assert_eq!(parse_toml("[processes]\nweb = echo\n").unwrap_err(),
+                   "line 2: expected `name = \"command\"`");
+    }
+}
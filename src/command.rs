@@ -0,0 +1,367 @@
+use traits::{Runner, Signal, Receiver};
+use ready::Ready;
+use {MaridError};
+use chan;
+use libc;
+use std::env;
+use std::fmt;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::mem;
+
+/// Error returned by a CommandRunner.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum CommandError {
+    /// The program could not be found, or is not executable.
+    NotFound(PathBuf),
+    /// The child exited with an unsuccessful status.
+    Exited(ExitStatus),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CommandError::NotFound(ref program) => {
+                write!(fmt, "program not found: {}", program.display())
+            },
+            CommandError::Exited(ref status) => write!(fmt, "child {}", status),
+        }
+    }
+}
+
+impl Error for CommandError {
+    fn description(&self) -> &str {
+        match *self {
+            CommandError::NotFound(_) => "program not found",
+            CommandError::Exited(_) => "child exited unsuccessfully",
+        }
+    }
+}
+
+type Output = Arc<Fn(&str) + Send + Sync>;
+
+/// A Runner supervising a child process.
+///
+/// `setup()` checks that the program exists and is executable, and `run()` spawns the
+/// child, sending it each Signal received as a real signal, until it exits. The child is
+/// started in its own process group, and signals are sent to the whole group, so that
+/// they also reach the processes it started, such as those of a shell command. Once the
+/// child has exited, any process it left running in its group is killed, so that none
+/// outlives the runner. The runner fails with a `CommandError::Exited` carrying the exit
+/// status of a child that did not exit successfully.
+///
+/// The output of the child is inherited, unless `output()` is used to receive each of
+/// its lines instead.
+///
+/// Besides Signals, the runner accepts any message converting into an `Option<Signal>`,
+/// so that it can be a member of a group with its own message type. Messages converting
+/// into None are not sent to the child.
+pub struct CommandRunner {
+    command: Command,
+    program: PathBuf,
+    prefix: String,
+    output: Option<Output>,
+}
+
+impl CommandRunner {
+    /// Creates a runner for the command, with the given name as the prefix of its output.
+    pub fn new<N: Into<String>>(name: N, command: Command) -> CommandRunner {
+        let program = PathBuf::from(command.get_program());
+        CommandRunner {
+            command: command,
+            program: program,
+            prefix: name.into(),
+            output: None,
+        }
+    }
+
+    /// Sets the prefix of each line of output, which defaults to the runner's name.
+    pub fn prefix<P: Into<String>>(mut self, prefix: P) -> CommandRunner {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Calls the function with each line written by the child to its stdout or stderr,
+    /// following the prefix and a `|` separator.
+    pub fn output<F>(mut self, output: F) -> CommandRunner where F: Fn(&str) + Send + Sync + 'static {
+        self.output = Some(Arc::new(output));
+        self
+    }
+
+    fn spawn(&mut self) -> io::Result<(Child, Vec<thread::JoinHandle<()>>)> {
+        self.command.process_group(0);
+        let output = match self.output {
+            Some(ref output) => output.clone(),
+            None => return Ok((try!(self.command.spawn()), vec!())),
+        };
+
+        let mut child = try!(self.command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn());
+        let mut readers = vec!();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(forward(stdout, self.prefix.clone(), output.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(forward(stderr, self.prefix.clone(), output));
+        }
+        Ok((child, readers))
+    }
+}
+
+// Calls the output function with each line read, until the pipe is closed.
+fn forward<R>(pipe: R, prefix: String, output: Output) -> thread::JoinHandle<()>
+    where R: Read + Send + 'static {
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines() {
+            match line {
+                Ok(line) => output(&format!("{} | {}", prefix, line)),
+                Err(_) => break,
+            }
+        }
+    })
+}
+
+impl<M> Runner<M> for CommandRunner where M: Into<Option<Signal>> + Send {
+    fn setup(&mut self) -> Result<(), MaridError> {
+        match find_program(&self.program) {
+            Some(_) => Ok(()),
+            None => Err(Box::new(CommandError::NotFound(self.program.clone()))),
+        }
+    }
+
+    fn run(self: Box<Self>, signals: Receiver<M>) -> Result<(), MaridError> {
+        self.run_ready(signals, Ready::unobserved())
+    }
+
+    fn run_ready(mut self: Box<Self>, signals: Receiver<M>, ready: Ready) -> Result<(), MaridError> {
+        let (mut child, readers) = try!(self.spawn());
+        ready.close();
+
+        // The exit of the child is awaited without reaping it, so that a signal is never
+        // sent to a process that reused its pid.
+        let pid = child.id();
+        let (exit_sn, exited) = chan::sync::<()>(0);
+        thread::spawn(move || {
+            wait_exited(pid);
+            drop(exit_sn);
+        });
+
+        let (_never_sn, never) = chan::sync(0);
+        let mut signals = signals;
+        loop {
+            let mut received = None;
+            chan_select! {
+                signals.recv() -> sig => received = Some(sig),
+                exited.recv() => {},
+            }
+            match received {
+                Some(Some(msg)) => if let Some(sig) = msg.into() {
+                    unsafe {
+                        libc::kill(-(pid as libc::pid_t), signo(sig));
+                    }
+                },
+                Some(None) => signals = never.clone(),
+                None => break,
+            }
+        }
+        // Until the child is reaped its pid, and so its group, cannot be reused. Processes
+        // left in the group would otherwise keep the output open.
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+        let status = try!(child.wait());
+
+        for reader in readers {
+            let _ = reader.join();
+        }
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Box::new(CommandError::Exited(status)))
+        }
+    }
+}
+
+// Blocks until the child has exited, leaving it to be reaped.
+fn wait_exited(pid: u32) {
+    loop {
+        let res = unsafe {
+            let mut info: libc::siginfo_t = mem::zeroed();
+            libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, libc::WEXITED | libc::WNOWAIT)
+        };
+        if res == 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return
+        }
+    }
+}
+
+// Returns the path of the program, searching the PATH unless it names a path, if it is
+// an executable file.
+fn find_program(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return if executable(program) { Some(program.to_path_buf()) } else { None }
+    }
+    let paths = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| executable(path))
+}
+
+fn executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+fn signo(signal: Signal) -> libc::c_int {
+    match signal {
+        Signal::HUP => libc::SIGHUP,
+        Signal::INT => libc::SIGINT,
+        Signal::QUIT => libc::SIGQUIT,
+        Signal::ILL => libc::SIGILL,
+        Signal::ABRT => libc::SIGABRT,
+        Signal::FPE => libc::SIGFPE,
+        Signal::KILL => libc::SIGKILL,
+        Signal::SEGV => libc::SIGSEGV,
+        Signal::PIPE => libc::SIGPIPE,
+        Signal::ALRM => libc::SIGALRM,
+        Signal::TERM => libc::SIGTERM,
+        Signal::USR1 => libc::SIGUSR1,
+        Signal::USR2 => libc::SIGUSR2,
+        Signal::CHLD => libc::SIGCHLD,
+        Signal::CONT => libc::SIGCONT,
+        Signal::STOP => libc::SIGSTOP,
+        Signal::TSTP => libc::SIGTSTP,
+        Signal::TTIN => libc::SIGTTIN,
+        Signal::TTOU => libc::SIGTTOU,
+        Signal::BUS => libc::SIGBUS,
+        Signal::PROF => libc::SIGPROF,
+        Signal::SYS => libc::SIGSYS,
+        Signal::TRAP => libc::SIGTRAP,
+        Signal::URG => libc::SIGURG,
+        Signal::VTALRM => libc::SIGVTALRM,
+        Signal::XCPU => libc::SIGXCPU,
+        Signal::XFSZ => libc::SIGXFSZ,
+        Signal::IO => libc::SIGIO,
+        Signal::WINCH => libc::SIGWINCH,
+        Signal::__NonExhaustiveMatch => unreachable!("Not a signal"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandRunner, CommandError};
+    use traits::{Runner, Signal};
+    use std::process::Command;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use chan;
+
+    #[test]
+    fn test_command_not_found() {
+        let mut runner = CommandRunner::new("missing", Command::new("marid-no-such-program"));
+        let err = Runner::<Signal>::setup(&mut runner).unwrap_err();
+        assert_eq!(err.downcast_ref::<CommandError>(),
+                   Some(&CommandError::NotFound(PathBuf::from("marid-no-such-program"))));
+        assert!(Runner::<Signal>::setup(&mut CommandRunner::new("dir", Command::new("/"))).is_err());
+    }
+
+    #[test]
+    fn test_command_output() {
+        let lines = Arc::new(Mutex::new(vec!()));
+        let output = lines.clone();
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo out; echo err >&2; exit 3");
+        let mut runner = Box::new(CommandRunner::new("sh", command)
+                                  .output(move |l| output.lock().unwrap().push(l.to_string())));
+        assert!(Runner::<Signal>::setup(&mut *runner).is_ok());
+
+        let (_sn, rc) = chan::sync::<Signal>(0);
+        let err = runner.run(rc).unwrap_err();
+        assert_eq!(err.downcast_ref::<CommandError>(),
+                   Some(&CommandError::Exited(ExitStatusExt::from_raw(3 << 8))));
+        let mut lines = lines.lock().unwrap().clone();
+        lines.sort();
+        assert_eq!(lines, vec!("sh | err", "sh | out"));
+    }
+
+    #[test]
+    fn test_command_signal_group() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 10; echo done");
+        let runner = Box::new(CommandRunner::new("sh", command).output(|_| {}));
+        let (sn, rc) = chan::sync(1);
+        sn.send(Signal::TERM);
+        // The output is only closed once the sleep has been signaled as well.
+        assert!(runner.run(rc).is_err());
+    }
+
+    #[test]
+    fn test_command_kills_group() {
+        let lines = Arc::new(Mutex::new(vec!()));
+        let output = lines.clone();
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 10 & echo started");
+        let runner = Box::new(CommandRunner::new("sh", command)
+                              .output(move |l| output.lock().unwrap().push(l.to_string())));
+
+        // The backgrounded sleep is killed once the shell exits, closing the output.
+        let start = Instant::now();
+        let (_sn, rc) = chan::sync::<Signal>(0);
+        assert!(runner.run(rc).is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(*lines.lock().unwrap(), vec!("sh | started"));
+    }
+
+    #[test]
+    fn test_command_signal() {
+        let mut command = Command::new("sleep");
+        command.arg("10");
+        let runner = Box::new(CommandRunner::new("sleep", command));
+        let (sn, rc) = chan::sync(1);
+        sn.send(Signal::TERM);
+        let err = runner.run(rc).unwrap_err();
+        match err.downcast_ref::<CommandError>() {
+            Some(&CommandError::Exited(status)) => assert_eq!(status.signal(), Some(15)),
+            _ => assert!(false, "Expected an exit status"),
+        }
+    }
+
+    // A message of an application, only some of which are signals.
+    enum Message {
+        Reload,
+        Signal(Signal),
+    }
+
+    impl From<Message> for Option<Signal> {
+        fn from(msg: Message) -> Option<Signal> {
+            match msg {
+                Message::Reload => None,
+                Message::Signal(sig) => Some(sig),
+            }
+        }
+    }
+
+    #[test]
+    fn test_command_messages() {
+        let mut command = Command::new("sleep");
+        command.arg("10");
+        let runner = Box::new(CommandRunner::new("sleep", command));
+        let (sn, rc) = chan::sync(2);
+        // Messages that are not signals are not sent to the child.
+        sn.send(Message::Reload);
+        sn.send(Message::Signal(Signal::TERM));
+        let err = runner.run(rc).unwrap_err();
+        match err.downcast_ref::<CommandError>() {
+            Some(&CommandError::Exited(status)) => assert_eq!(status.signal(), Some(15)),
+            _ => assert!(false, "Expected an exit status"),
+        }
+    }
+}
@@ -1,19 +1,358 @@
-use crossbeam;
 use traits::{Runner, Signal, Receiver, Sender};
+use panic::{catch_runner, PanicError};
+use ready::Ready;
+use events::{self, Events, Event, EventKind, outcome_event};
+use logging;
+use metrics::Metrics;
+use shutdown::{Escalation, StillRunning};
 use {MaridError};
 use chan;
 use std::thread;
-use std::sync::{Arc, Mutex};
+use std::cmp;
+use std::mem;
+use std::fmt;
+use std::error::Error;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+
+type Exit = (usize, Result<(), MaridError>);
+// The index of a member that failed, along with its error.
+type Failure = (usize, MaridError);
+pub type Reply = mpsc::Sender<Result<(), MaridError>>;
+// A runner added through a GroupHandle, once its setup has returned.
+type Setup<M> = (usize, Box<Runner<M> + Send>, Result<(), MaridError>, Reply);
 
 /// The Composer type.
 ///
 /// The Composer will start each runner inside of its own thread when the run() function
-/// is called. The current behavior is an ordered setup/run, but in the future a parallel
-/// startup mode will be offered.
-pub struct Composer<R> {
+/// is called. By default runners are setup one after another, in the order they were
+/// given; see `StartupMode` for the alternatives.
+///
+/// A member that panics, during setup or while running, fails with a `PanicError` and
+/// the group is torn down as with any other error.
+///
+/// Members can be given a name with `member()`, which is used in error reports and
+/// allows a single member to be signaled through a `GroupHandle`.
+///
+/// Members can also be added and removed while the Composer is running through a
+/// `GroupHandle`, see `handle()`.
+///
+/// Signals received by the Composer are delivered to every running member, unless a
+/// named member has been given a Route, see `route()`.
+///
+/// The lifecycle events of every member can be received through `subscribe()`.
+///
+/// Members are started with `Runner::run_ready()`. The Composer closes its own Ready
+/// handle once every running member has closed theirs.
+pub struct Composer<R, M = Signal> {
     runners: Vec<R>,
+    names: BTreeMap<usize, Option<String>>,
+    members: BTreeMap<usize, Member<M>>,
+    next_index: usize,
+    pending: Vec<usize>,
     state: State,
-    error_signal: Signal,
+    error_signal: M,
+    startup: StartupMode,
+    shutdown: Option<ShutdownPolicy>,
+    escalation: Option<Escalation<M>>,
+    routes: HashMap<String, Route<M>>,
+    events: Events<M>,
+    failures: Vec<MemberError>,
+    // Whether the first failure tore down the group, rather than its shutdown signal.
+    triggered: bool,
+    // Set once the group has been torn down by its shutdown signal.
+    signaled: bool,
+    exit_send: Sender<Exit>,
+    exit_recv: Receiver<Exit>,
+    ready_send: Sender<usize>,
+    ready_recv: Receiver<usize>,
+    setup_send: Sender<Setup<M>>,
+    setup_recv: Receiver<Setup<M>>,
+    command_send: Option<Sender<Command<M>>>,
+    command_recv: Option<Receiver<Command<M>>>,
+    closed: Arc<Mutex<bool>>,
+}
+
+/// Determines how a Composer calls setup() on its runners.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum StartupMode {
+    /// Setup each runner in turn, in the order they were given to the Composer.
+    Sequential,
+    /// Setup every runner at the same time, each on its own thread.
+    ///
+    /// The Composer returns as soon as any runner fails its setup, without waiting
+    /// for the remaining runners to finish.
+    Parallel,
+    /// Setup each runner in turn, starting its run() before the next runner is setup.
+    ///
+    /// The next runner is setup once the previous one has closed its Ready handle, or
+    /// has exited. Unless another ShutdownPolicy is given, the group is shut down
+    /// with `ShutdownPolicy::Reverse(None)`, which waits on each member without a
+    /// deadline; see `ShutdownPolicy::Reverse`.
+    Ordered,
+}
+
+/// Determines how a Composer delivers its shutdown signals to its runners.
+///
+/// The shutdown signals are the error_signal and the shutdown signal of the Escalation,
+/// if any. Every other signal is sent to every running member at the same time.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ShutdownPolicy {
+    /// Send the signal to every running member at the same time.
+    Simultaneous,
+    /// Send the signal to one member at a time, in reverse order of declaration.
+    ///
+    /// The Composer waits for a member's run() to return before signaling the next one.
+    /// Members whose Route drops the signal are skipped.
+    /// When a grace period is given, the Composer moves on to the next member once it
+    /// has elapsed, even if the member is still running.
+    ///
+    /// Without a grace period, a member that ignores the signal is waited on forever.
+    /// Meanwhile the Composer neither handles further signals nor GroupHandle commands.
+    /// Give the Composer an `Escalation` to bound the wait for each member.
+    Reverse(Option<Duration>),
+}
+
+/// Determines which of the signals received by a Composer reach a member.
+///
+/// A signal is first mapped, then dropped if it is ignored, or if the Route only lets
+/// through other signals. By default a Route lets every signal through unchanged.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Route<M = Signal> {
+    only: Option<Vec<M>>,
+    ignore: Vec<M>,
+    map: Vec<(M, M)>,
+}
+
+impl<M> Route<M> where M: Clone + PartialEq {
+    /// Creates a Route letting every signal through.
+    pub fn new() -> Route<M> {
+        Route {
+            only: None,
+            ignore: vec!(),
+            map: vec!(),
+        }
+    }
+
+    /// Only lets the given signals through.
+    pub fn only(mut self, signals: Vec<M>) -> Route<M> {
+        self.only = Some(signals);
+        self
+    }
+
+    /// Drops the signal.
+    pub fn ignore(mut self, signal: M) -> Route<M> {
+        self.ignore.push(signal);
+        self
+    }
+
+    /// Delivers the `to` signal in place of the `from` signal.
+    pub fn map(mut self, from: M, to: M) -> Route<M> {
+        self.map.push((from, to));
+        self
+    }
+
+    /// Returns the signal to deliver in place of the given one, if any.
+    pub fn apply(&self, signal: &M) -> Option<M> {
+        let signal = self.map.iter()
+            .find(|&&(ref from, _)| from == signal)
+            .map_or(signal, |&(_, ref to)| to);
+        if self.ignore.contains(signal) {
+            return None
+        }
+        match self.only {
+            Some(ref only) if !only.contains(signal) => None,
+            _ => Some(signal.clone()),
+        }
+    }
+}
+
+impl<M> Default for Route<M> where M: Clone + PartialEq {
+    fn default() -> Route<M> {
+        Route::new()
+    }
+}
+
+/// The failure of a single member of a Composer.
+#[derive(Debug)]
+pub struct MemberError {
+    /// Index of the failing member, in the order it was given to the Composer.
+    pub index: usize,
+    /// Name of the failing member, if it was given one.
+    pub name: Option<String>,
+    /// The error returned by the member.
+    pub error: MaridError,
+}
+
+impl fmt::Display for MemberError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(fmt, "member {} ({}): {}", name, self.index, self.error),
+            None => write!(fmt, "member {}: {}", self.index, self.error),
+        }
+    }
+}
+
+impl Error for MemberError {
+    fn description(&self) -> &str {
+        self.error.description()
+    }
+}
+
+/// Error returned by a Composer when one or more of its members fail.
+///
+/// Failures are listed in the order they occurred. Unless the group was torn down by its
+/// shutdown signal, the first failure is the one that caused the rest of the group to be
+/// torn down, see `trigger()`.
+#[derive(Debug)]
+pub struct GroupError {
+    failures: Vec<MemberError>,
+    triggered: bool,
+}
+
+impl GroupError {
+    /// Returns every member failure, in the order they occurred.
+    pub fn failures(&self) -> &[MemberError] {
+        &self.failures
+    }
+
+    /// Returns the failure that caused the group to be torn down, or None if the group
+    /// was torn down by its shutdown signal, in which case every failure is a consequence
+    /// of the teardown.
+    pub fn trigger(&self) -> Option<&MemberError> {
+        if self.triggered {
+            self.failures.first()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the members that were given up on by an Escalation, and are still running.
+    pub fn still_running(&self) -> Vec<&MemberError> {
+        self.failures.iter().filter(|f| f.error.is::<StillRunning>()).collect()
+    }
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, "{} member(s) failed: ", self.failures.len()));
+        for (i, f) in self.failures.iter().enumerate() {
+            if i > 0 {
+                try!(write!(fmt, "; "));
+            }
+            try!(write!(fmt, "{}", f));
+        }
+        Ok(())
+    }
+}
+
+impl Error for GroupError {
+    fn description(&self) -> &str {
+        "one or more members failed"
+    }
+}
+
+/// Error returned by a GroupHandle or a SupervisorHandle when a command could not be
+/// applied to the group.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum MembershipError {
+    /// The Composer or Supervisor is shutting down, or has exited.
+    Closed,
+    /// A running member already has the given name.
+    DuplicateName(String),
+    /// No running member has the given name.
+    UnknownMember(String),
+}
+
+impl fmt::Display for MembershipError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MembershipError::Closed => write!(fmt, "group is closed"),
+            MembershipError::DuplicateName(ref name) => write!(fmt, "duplicate member name: {}", name),
+            MembershipError::UnknownMember(ref name) => write!(fmt, "no running member named {}", name),
+        }
+    }
+}
+
+impl Error for MembershipError {
+    fn description(&self) -> &str {
+        match *self {
+            MembershipError::Closed => "group is closed",
+            MembershipError::DuplicateName(_) => "duplicate member name",
+            MembershipError::UnknownMember(_) => "no running member with the given name",
+        }
+    }
+}
+
+enum Command<M> {
+    Add(String, Box<Runner<M> + Send>, Reply),
+    Remove(String, M, Reply),
+    Signal(String, M, Reply),
+}
+
+/// A handle used to add, remove and signal members of a running Composer.
+///
+/// Handles are created with `Composer::handle()` and can be cloned and sent to other
+/// threads. Commands given before the Composer has started running are handled once
+/// it does.
+pub struct GroupHandle<M = Signal> {
+    commands: Sender<Command<M>>,
+    closed: Arc<Mutex<bool>>,
+}
+
+impl<M> Clone for GroupHandle<M> {
+    fn clone(&self) -> GroupHandle<M> {
+        GroupHandle {
+            commands: self.commands.clone(),
+            closed: self.closed.clone(),
+        }
+    }
+}
+
+impl<M> GroupHandle<M> {
+    /// Adds a named runner to the group.
+    ///
+    /// The runner is setup on its own thread and started by the Composer, and receives
+    /// the signals delivered to the group from then on. Blocks until the runner has
+    /// started, or returns the error of its setup, in which case the rest of the group is
+    /// unaffected. Once running, a failure of the runner tears down the group as with any
+    /// member. Once it has exited, the runner is forgotten, and its name can be reused.
+    pub fn add(&self, name: &str, runner: Box<Runner<M> + Send>) -> Result<(), MaridError> {
+        self.request(|reply| Command::Add(name.to_string(), runner, reply))
+    }
+
+    /// Sends the signal to the named member and removes it from the group.
+    ///
+    /// Blocks until the member has exited. Its result is ignored, so that it does not tear
+    /// down the group, and a `Removed` event is emitted for it. Fails with
+    /// `MembershipError::UnknownMember` if no running member has the given name, and with
+    /// `StillRunning` if the member is given up on by an Escalation. A member must not
+    /// remove itself, as it would wait for its own exit.
+    pub fn remove(&self, name: &str, signal: M) -> Result<(), MaridError> {
+        self.request(|reply| Command::Remove(name.to_string(), signal, reply))
+    }
+
+    /// Sends the signal to the named member only.
+    ///
+    /// The signal reaches the member unchanged, whatever its Route. Fails with
+    /// `MembershipError::UnknownMember` if no running member has the given name.
+    pub fn signal(&self, name: &str, signal: M) -> Result<(), MaridError> {
+        self.request(|reply| Command::Signal(name.to_string(), signal, reply))
+    }
+
+    // Sends the command to the Composer, and waits for its reply.
+    fn request<F>(&self, command: F) -> Result<(), MaridError> where F: FnOnce(Reply) -> Command<M> {
+        let (sn, rc) = mpsc::channel();
+        {
+            let closed = self.closed.lock().unwrap_or_else(|e| e.into_inner());
+            if *closed {
+                return Err(Box::new(MembershipError::Closed))
+            }
+            self.commands.send(command(sn));
+        }
+        rc.recv().unwrap_or_else(|_| Err(Box::new(MembershipError::Closed)))
+    }
 }
 
 enum State {
@@ -21,126 +360,821 @@ enum State {
     SetupDone,
 }
 
-impl<R> Composer<R> {
+// A runner that has been started on its own thread.
+struct Member<M> {
+    signals: Sender<M>,
+    handle: Option<thread::JoinHandle<()>>,
+    running: bool,
+    // Replies to the removals of the member, sent once it has exited.
+    removals: Vec<Reply>,
+    ready: bool,
+    // Added through a GroupHandle, and forgotten once it has exited.
+    added: bool,
+}
+
+enum LoopEvent<M> {
+    Signal(Option<M>),
+    Exit(Exit),
+    Command(Option<Command<M>>),
+    Ready(usize),
+    Setup(Setup<M>),
+}
+
+impl<R, M> Composer<R, M> {
     /// Creates a new Composer.
     ///
     /// The error_signal is the Signal that the Composer will send to
     /// runners when another runner in the group has finished with an error.
-    pub fn new(runners: Vec<R>, error_signal: Signal) -> Composer<R> {
+    pub fn new(runners: Vec<R>, error_signal: M) -> Composer<R, M> {
+        let (exit_send, exit_recv) = chan::async();
+        let (ready_send, ready_recv) = chan::async();
+        let (setup_send, setup_recv) = chan::async();
+        let names = (0..runners.len()).map(|idx| (idx, None)).collect();
+        let next_index = runners.len();
         Composer{
             runners: runners,
+            names: names,
+            members: BTreeMap::new(),
+            next_index: next_index,
+            pending: vec!(),
             state: State::Init,
             error_signal: error_signal,
+            startup: StartupMode::Sequential,
+            shutdown: None,
+            escalation: None,
+            routes: HashMap::new(),
+            events: Events::new(),
+            failures: vec!(),
+            triggered: false,
+            signaled: false,
+            exit_send: exit_send,
+            exit_recv: exit_recv,
+            ready_send: ready_send,
+            ready_recv: ready_recv,
+            setup_send: setup_send,
+            setup_recv: setup_recv,
+            command_send: None,
+            command_recv: None,
+            closed: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    /// Adds a named runner to the Composer, after any runners already given.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a member with the same name has already been added.
+    pub fn member(mut self, name: &str, runner: R) -> Composer<R, M> {
+        assert!(!self.names.values().any(|n| n.as_ref().map_or(false, |n| n == name)),
+                "Duplicate member name: {}", name);
+        self.runners.push(runner);
+        self.names.insert(self.next_index, Some(name.to_string()));
+        self.next_index += 1;
+        self
+    }
+
+    /// Sets the StartupMode used when setting up the runners.
+    pub fn startup(mut self, mode: StartupMode) -> Composer<R, M> {
+        self.startup = mode;
+        self
+    }
+
+    /// Sets the ShutdownPolicy used when signaling the runners.
+    ///
+    /// Defaults to `ShutdownPolicy::Simultaneous`, except for the `StartupMode::Ordered`
+    /// mode which defaults to `ShutdownPolicy::Reverse(None)`.
+    pub fn shutdown(mut self, policy: ShutdownPolicy) -> Composer<R, M> {
+        self.shutdown = Some(policy);
+        self
+    }
+
+    /// Sets an Escalation used whenever the group is torn down.
+    ///
+    /// The Escalation starts when the Composer receives its shutdown signal, or when a
+    /// member fails and the error_signal is sent in its place. Once the grace period
+    /// has elapsed the force signal is sent to every member still running. Members that
+    /// are still running after the deadline are reported in the GroupError as
+    /// `StillRunning`, and left running in the background.
+    ///
+    /// With a `ShutdownPolicy::Reverse` policy, the grace period bounds the wait for each
+    /// member in turn, and the force signal is sent once every member has been signaled.
+    pub fn escalate(mut self, escalation: Escalation<M>) -> Composer<R, M> {
+        self.escalation = Some(escalation);
+        self
+    }
+
+    /// Sets the Route of the named member, including members added through a GroupHandle.
+    ///
+    /// Routes apply to the signals received by the Composer. Signals sent by the Composer
+    /// itself when tearing down the group, and signals sent to a single member through
+    /// a GroupHandle, reach the member unchanged.
+    pub fn route(mut self, name: &str, route: Route<M>) -> Composer<R, M> {
+        self.routes.insert(name.to_string(), route);
+        self
+    }
+
+    /// Returns a Receiver of the lifecycle events of the members, tagged with their
+    /// index and name.
+    ///
+    /// Subscribing before the Composer is started receives every event, including those
+    /// of the runner started by a MaridProcess.
+    pub fn subscribe(&self) -> mpsc::Receiver<Event<M>> {
+        self.events.subscribe()
+    }
+
+    /// Returns the Metrics registry of the members.
+    ///
+    /// Once started by a MaridProcess, the registry records the runner started by the
+    /// MaridProcess as well.
+    pub fn metrics(&self) -> Metrics {
+        self.events.metrics()
+    }
+
+    /// Returns a GroupHandle used to add, remove and signal members while the Composer runs.
+    ///
+    /// A Composer that has given out a handle keeps running when it has no running
+    /// members, until it receives a shutdown signal, a member fails, or every handle has
+    /// been dropped. From then on, runners can no longer be added.
+    pub fn handle(&mut self) -> GroupHandle<M> {
+        if self.command_send.is_none() {
+            let (sn, rc) = chan::async();
+            self.command_send = Some(sn);
+            self.command_recv = Some(rc);
+        }
+        GroupHandle {
+            commands: self.command_send.clone().expect("Command channel missing"),
+            closed: self.closed.clone(),
         }
     }
 
-    fn take_runners_and_setup_signal_chan(self) -> (Vec<(R, Receiver<Signal>)>, Vec<Sender<Signal>>) {
-        let mut runners_vec = vec!();
-        let mut sender_vec = vec!();
-        for r in self.runners.into_iter() {
-            let (sn, rc) = chan::sync(1024);
-            runners_vec.push((r, rc));
-            sender_vec.push(sn);
+    fn shutdown_policy(&self) -> ShutdownPolicy {
+        match (self.shutdown, self.startup) {
+            (Some(policy), _) => policy,
+            (None, StartupMode::Ordered) => ShutdownPolicy::Reverse(None),
+            (None, _) => ShutdownPolicy::Simultaneous,
         }
-        (runners_vec, sender_vec)
     }
 }
 
-impl Runner for Composer<Box<Runner + Send>> {
-    fn run(mut self: Box<Self>, signals: Receiver<Signal>) -> Result<(), MaridError> {
-        match self.state {
-            State::Init => try!(self.setup()),
-            _ => {},
+impl<M> Composer<Box<Runner<M> + Send>, M> where M: Clone + PartialEq + fmt::Debug + Send + 'static {
+    // Spawns the run thread of a runner that has been setup, returning once the thread
+    // has started.
+    fn spawn(&mut self, idx: usize, runner: Box<Runner<M> + Send>, added: bool) {
+        self.member_events(idx).emit(EventKind::Running);
+        let (sn, rc) = chan::sync(1024);
+        let (started_sn, started_rc) = mpsc::channel();
+        let exits = self.exit_send.clone();
+        let readies = self.ready_send.clone();
+        let ready_events = self.member_events(idx);
+        let ready = Ready::new(move || {
+            ready_events.emit(EventKind::Ready);
+            readies.send(idx)
+        });
+        let handle = thread::spawn(move || {
+            let _ = started_sn.send(());
+            let res = catch_runner(move || runner.run_ready(rc, ready));
+            exits.send((idx, res));
+        });
+        let _ = started_rc.recv();
+
+        self.members.insert(idx, Member {
+            signals: sn,
+            handle: Some(handle),
+            running: true,
+            removals: vec!(),
+            ready: false,
+            added: added,
+        });
+    }
+
+    fn member_events(&self, idx: usize) -> Events<M> {
+        self.events.member(idx, self.names[&idx].as_ref().map(|n| &n[..]))
+    }
+
+    // Signals a single member.
+    fn send(&self, idx: usize, signal: M) {
+        self.member_events(idx).emit(EventKind::SignalDelivered(signal.clone()));
+        self.members[&idx].signals.send(signal);
+    }
+
+    fn all_ready(&self) -> bool {
+        self.members.values().all(|m| m.ready || !m.running)
+    }
+
+    fn mark_ready(&mut self, idx: usize) {
+        if let Some(member) = self.members.get_mut(&idx) {
+            member.ready = true;
         }
+    }
 
-        let error_signal = self.error_signal;
-        let (runners_vec, sender_vec) = self.take_runners_and_setup_signal_chan();
-        let (stop_sn, stop_rc) = chan::sync(0);
-        let (error_sn, error_rc) = chan::sync(1);
-        let signaling = signaling_thread(signals,
-                                         sender_vec,
-                                         stop_rc,
-                                         error_signal,
-                                         error_rc);
-        let error = Arc::new(Mutex::new(None));
-        let error_clone = error.clone();
-
-        crossbeam::scope(|scope| {
-            for (r, rc) in runners_vec.into_iter() {
-                let err = error_clone.clone();
-                let err_sn = error_sn.clone();
-                scope.spawn(move || {
-                    match r.run(rc) {
-                        Ok(()) => {},
-                        Err(e) => {
-                            let mut guard = err.lock().unwrap();
-                            *guard = Some(e);
-                            err_sn.send(true);
-                        },
-                    }
-                });
+    // Returns the indexes of the running members, in order.
+    fn running_members(&self) -> Vec<usize> {
+        self.members.iter().filter(|&(_, m)| m.running).map(|(idx, _)| *idx).collect()
+    }
+
+    // Waits until the member has closed its Ready handle or exited, recording any other
+    // member that exits meanwhile. Returns true if a member failed.
+    fn wait_ready(&mut self, idx: usize) -> bool {
+        let exits = self.exit_recv.clone();
+        let readies = self.ready_recv.clone();
+        while self.members[&idx].running && !self.members[&idx].ready {
+            let event: LoopEvent<M>;
+            chan_select! {
+                exits.recv() -> exit => event = LoopEvent::Exit(exit.expect("Exit channel closed")),
+                readies.recv() -> i => event = LoopEvent::Ready(i.expect("Ready channel closed")),
             }
+            let failed = match event {
+                LoopEvent::Exit(exit) => self.record(exit),
+                LoopEvent::Ready(i) => {
+                    self.mark_ready(i);
+                    false
+                },
+                _ => false,
+            };
+            if failed {
+                return true
+            }
+        }
+        false
+    }
+
+    // Tears down the members started during setup, returning the group's error.
+    fn abort_setup(&mut self) -> Result<(), MaridError> {
+        let error_signal = self.error_signal.clone();
+        self.teardown(error_signal, false);
+        while self.running() > 0 {
+            let exit = self.exit_recv.recv().expect("Exit channel closed");
+            self.record(exit);
+        }
+        self.join();
+        self.result()
+    }
+
+    fn running(&self) -> usize {
+        self.members.values().filter(|m| m.running).count()
+    }
+
+    // Marks the member as exited, returning whether it failed. Exits of members that
+    // have been given up on are ignored.
+    fn record(&mut self, exit: Exit) -> bool {
+        let (idx, res) = exit;
+        let (removals, added) = match self.members.get_mut(&idx) {
+            Some(member) if member.running => {
+                member.running = false;
+                (mem::take(&mut member.removals), member.added)
+            },
+            _ => return false,
+        };
+        let removed = !removals.is_empty();
+        self.member_events(idx).emit(outcome_event(&res, EventKind::Exited));
+        if removed || added {
+            self.member_events(idx).emit(EventKind::Removed);
+        }
+        for reply in removals {
+            let _ = reply.send(Ok(()));
+        }
+        let failed = match res {
+            _ if removed => false,
+            Ok(()) => false,
+            Err(e) => {
+                self.fail(idx, e);
+                true
+            },
+        };
+        self.reclaim(idx);
+        failed
+    }
+
+    // Joins the thread of an exited member added through a GroupHandle, and forgets it.
+    fn reclaim(&mut self, idx: usize) {
+        if !self.members[&idx].added {
+            return
+        }
+        if let Some(handle) = self.members.remove(&idx).and_then(|m| m.handle) {
+            let _ = handle.join();
+        }
+        self.names.remove(&idx);
+    }
+
+    // Records the failure of a member. The first one tears down the group, unless the
+    // shutdown signal already has.
+    fn fail(&mut self, index: usize, error: MaridError) {
+        if self.failures.is_empty() {
+            self.triggered = !self.signaled;
+        }
+        let name = self.names[&index].clone();
+        self.failures.push(MemberError {
+            index: index,
+            name: name,
+            error: error,
         });
+    }
 
-        stop_sn.send(true);
-        signaling.join().unwrap();
-        take_error(error)
+    fn result(&mut self) -> Result<(), MaridError> {
+        if self.failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(group_error(mem::take(&mut self.failures), self.triggered)))
+        }
     }
 
-    fn setup(&mut self) -> Result<(), MaridError> {
-        for r in self.runners.iter_mut() {
-            try!(r.setup());
+    // Returns the signal the member receives in place of the given one, following its
+    // Route if the signal is routed.
+    fn signal_for(&self, idx: usize, signal: &M, routed: bool) -> Option<M> {
+        let route = self.names[&idx].as_ref().and_then(|n| self.routes.get(n));
+        match route {
+            Some(route) if routed => route.apply(signal),
+            _ => Some(signal.clone()),
         }
-        self.state = State::SetupDone;
-        Ok(())
     }
-}
 
-fn take_error(err: Arc<Mutex<Option<MaridError>>>) -> Result<(), MaridError> {
-        match err.lock().unwrap().take() {
-            Some(e) => Err(e),
-            None => Ok(()),
+    // Returns whether the signal shuts down the group: the error_signal, or the shutdown
+    // signal of the Escalation.
+    fn is_shutdown(&self, signal: &M) -> bool {
+        *signal == self.error_signal || self.escalation.as_ref().map_or(false, |e| e.shutdown == *signal)
+    }
+
+    // Sends the signal to every running member at once.
+    fn broadcast(&self, signal: M, routed: bool) {
+        for idx in self.running_members() {
+            if let Some(signal) = self.signal_for(idx, &signal, routed) {
+                self.send(idx, signal);
+            }
+        }
+    }
+
+    // Delivers a shutdown signal following the ShutdownPolicy.
+    fn stop(&mut self, signal: M, routed: bool) {
+        match self.shutdown_policy() {
+            ShutdownPolicy::Simultaneous => self.broadcast(signal, routed),
+            ShutdownPolicy::Reverse(grace) => self.stop_in_reverse(signal, routed, grace),
+        }
+    }
+
+    // Stops the group with the shutdown signal, then follows the Escalation, if any,
+    // until every member has exited or been given up on.
+    //
+    // With an Escalation, the grace period bounds the wait for each member of a Reverse
+    // policy, and for the whole group otherwise.
+    fn teardown(&mut self, signal: M, routed: bool) {
+        logging::teardown(&events::label(&self.events), &signal);
+        let escalation = match self.escalation.clone() {
+            Some(escalation) => escalation,
+            None => return self.stop(signal, routed),
+        };
+
+        let stopped = match self.shutdown_policy() {
+            ShutdownPolicy::Simultaneous => {
+                self.broadcast(signal, routed);
+                self.wait_running(escalation.grace)
+            },
+            ShutdownPolicy::Reverse(grace) => {
+                let grace = grace.map_or(escalation.grace, |g| cmp::min(g, escalation.grace));
+                self.stop_in_reverse(signal, routed, Some(grace));
+                self.running() == 0
+            },
+        };
+        if stopped {
+            return
+        }
+        logging::escalate(&events::label(&self.events), &escalation.force, escalation.grace);
+        for idx in self.running_members() {
+            self.send(idx, escalation.force.clone());
+        }
+        if !self.wait_running(escalation.deadline) {
+            self.abandon();
+        }
+    }
+
+    // Waits for every member to exit, returning false if the timeout elapses first.
+    fn wait_running(&mut self, timeout: Duration) -> bool {
+        let timer = chan::after(timeout);
+        while self.running() > 0 {
+            match self.next_exit(Some(&timer)) {
+                Some(exit) => self.record(exit),
+                None => return false,
+            };
+        }
+        true
+    }
+
+    // Gives up on every running member, leaving its thread running.
+    fn abandon(&mut self) {
+        for idx in self.running_members() {
+            if let Some(member) = self.members.get_mut(&idx) {
+                member.running = false;
+                member.handle.take();
+                for reply in member.removals.drain(..) {
+                    let _ = reply.send(Err(Box::new(StillRunning)));
+                }
+            }
+            logging::abandon(&events::label(&self.member_events(idx)));
+            self.fail(idx, Box::new(StillRunning));
+        }
+    }
+
+    // Signals each running member in reverse order, waiting for it to exit, or for the
+    // grace period to elapse, before moving on to the next one.
+    fn stop_in_reverse(&mut self, signal: M, routed: bool, grace: Option<Duration>) {
+        for idx in self.running_members().into_iter().rev() {
+            // The member may have exited while the previous one was being stopped.
+            if !self.members.get(&idx).map_or(false, |m| m.running) {
+                continue
+            }
+            match self.signal_for(idx, &signal, routed) {
+                Some(signal) => self.send(idx, signal),
+                None => continue,
+            }
+            let timeout = grace.map(chan::after);
+            while self.members.get(&idx).map_or(false, |m| m.running) {
+                match self.next_exit(timeout.as_ref()) {
+                    Some(exit) => self.record(exit),
+                    None => break,
+                };
+            }
+        }
+    }
+
+    // Returns the index of the running member with the given name.
+    fn find_running(&self, name: &str) -> Option<usize> {
+        self.members.iter()
+            .find(|&(idx, m)| m.running && self.names[idx].as_ref().map_or(false, |n| n == name))
+            .map(|(idx, _)| *idx)
+    }
+
+    fn command(&mut self, command: Command<M>, closed: bool) {
+        match command {
+            Command::Add(name, runner, reply) => {
+                let duplicate = self.find_running(&name).is_some() || self.pending.iter()
+                    .any(|idx| self.names[idx].as_ref().map_or(false, |n| *n == name));
+                if closed {
+                    let _ = reply.send(Err(Box::new(MembershipError::Closed)));
+                } else if duplicate {
+                    let _ = reply.send(Err(Box::new(MembershipError::DuplicateName(name))));
+                } else {
+                    self.setup_added(name, runner, reply);
+                }
+            },
+            Command::Remove(name, signal, reply) => match self.find_running(&name) {
+                Some(idx) => {
+                    if let Some(member) = self.members.get_mut(&idx) {
+                        member.removals.push(reply);
+                    }
+                    self.send(idx, signal);
+                },
+                None => {
+                    let _ = reply.send(Err(unknown_member(name)));
+                },
+            },
+            Command::Signal(name, signal, reply) => {
+                let res = self.find_running(&name).map(|idx| self.send(idx, signal));
+                let _ = reply.send(res.ok_or_else(|| unknown_member(name)));
+            },
+        }
+    }
+
+    // Sets up a runner added through a GroupHandle on its own thread, reserving its index
+    // and name meanwhile.
+    fn setup_added(&mut self, name: String, runner: Box<Runner<M> + Send>, reply: Reply) {
+        let idx = self.next_index;
+        self.next_index += 1;
+        self.names.insert(idx, Some(name));
+        self.pending.push(idx);
+
+        let events = self.member_events(idx);
+        let setups = self.setup_send.clone();
+        thread::spawn(move || {
+            let mut runner = runner;
+            runner.observe(events.clone());
+            let res = setup_member(&mut runner, &events);
+            setups.send((idx, runner, res, reply));
+        });
+    }
+
+    // Starts a runner added through a GroupHandle once it has been setup, unless the
+    // group has been closed meanwhile.
+    fn start_added(&mut self, setup: Setup<M>, closed: bool) {
+        let (idx, runner, res, reply) = setup;
+        self.pending.retain(|&i| i != idx);
+        let res = match res {
+            Ok(()) if closed => Err(Box::new(MembershipError::Closed) as MaridError),
+            res => res,
+        };
+        if res.is_ok() {
+            self.spawn(idx, runner, true);
+        } else {
+            self.names.remove(&idx);
+        }
+        let _ = reply.send(res);
+    }
+
+    // Waits for the next member to exit, returning None if the timeout fires first.
+    fn next_exit(&self, timeout: Option<&Receiver<()>>) -> Option<Exit> {
+        let exits = &self.exit_recv;
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return Some(exits.recv().expect("Exit channel closed")),
+        };
+
+        let mut exit = None;
+        chan_select! {
+            exits.recv() -> res => exit = Some(res.expect("Exit channel closed")),
+            timeout.recv() => {},
+        }
+        exit
+    }
+
+    fn join(&mut self) {
+        for m in self.members.values_mut() {
+            if let Some(handle) = m.handle.take() {
+                let _ = handle.join();
+            }
         }
+    }
 }
 
-fn signaling_thread(signals: Receiver<Signal>,
-                    senders: Vec<Sender<Signal>>,
-                    quit: Receiver<bool>,
-                    error_signal: Signal,
-                    error: Receiver<bool>) -> thread::JoinHandle<()> {
-    thread::spawn(move || {
-        loop {
+impl<M> Runner<M> for Composer<Box<Runner<M> + Send>, M> where M: Clone + PartialEq + fmt::Debug + Send + 'static {
+    fn run(self: Box<Self>, signals: Receiver<M>) -> Result<(), MaridError> {
+        self.run_ready(signals, Ready::unobserved())
+    }
+
+    fn run_ready(mut self: Box<Self>, signals: Receiver<M>, ready: Ready) -> Result<(), MaridError> {
+        match self.state {
+            State::Init => try!(self.setup()),
+            _ => {},
+        }
+
+        for (idx, r) in mem::take(&mut self.runners).into_iter().enumerate() {
+            self.spawn(idx, r, false);
+        }
+
+        let mut signals = signals;
+        // Swapped in for the signal channel once it has been closed.
+        let (_closed_sn, closed_rc) = chan::sync(0);
+        let exits = self.exit_recv.clone();
+        let readies = self.ready_recv.clone();
+        let setups = self.setup_recv.clone();
+        let mut ready = Some(ready);
+        // Only the handles keep the command channel open once the Composer is running.
+        self.command_send = None;
+        let (_no_commands_sn, no_commands) = chan::sync(0);
+        let dynamic = self.command_recv.is_some();
+        let mut commands = self.command_recv.clone().unwrap_or_else(|| no_commands.clone());
+        let mut torn_down = false;
+        let mut closed = false;
+
+        while self.running() > 0 || !self.pending.is_empty() || (dynamic && !closed) {
+            if self.all_ready() {
+                if let Some(ready) = ready.take() {
+                    ready.close();
+                }
+            }
+
+            let event;
             chan_select! {
-                signals.recv() -> res => {
-                    let sig = match res {
-                        Some(s) => s,
-                        None => continue,
-                    };
-
-                    for sn in senders.iter() {
-                        sn.send(sig);
+                signals.recv() -> sig => event = LoopEvent::Signal(sig),
+                exits.recv() -> exit => event = LoopEvent::Exit(exit.expect("Exit channel closed")),
+                commands.recv() -> cmd => event = LoopEvent::Command(cmd),
+                readies.recv() -> i => event = LoopEvent::Ready(i.expect("Ready channel closed")),
+                setups.recv() -> s => event = LoopEvent::Setup(s.expect("Setup channel closed")),
+            }
+
+            match event {
+                LoopEvent::Signal(Some(sig)) => {
+                    if self.is_shutdown(&sig) {
+                        closed = true;
+                        self.signaled = true;
+                        self.teardown(sig, true);
+                    } else {
+                        self.broadcast(sig, true);
                     }
                 },
-                error.recv() => {
-                    for sn in senders.iter() {
-                        sn.send(error_signal);
+                LoopEvent::Signal(None) => signals = closed_rc.clone(),
+                LoopEvent::Command(Some(cmd)) => self.command(cmd, closed),
+                LoopEvent::Ready(idx) => self.mark_ready(idx),
+                LoopEvent::Setup(setup) => self.start_added(setup, closed),
+                LoopEvent::Command(None) => {
+                    closed = true;
+                    commands = no_commands.clone();
+                },
+                LoopEvent::Exit(exit) => {
+                    if self.record(exit) && !torn_down {
+                        closed = true;
+                        torn_down = true;
+                        let error_signal = self.error_signal.clone();
+                        self.teardown(error_signal, false);
                     }
                 },
-                quit.recv() => {
-                    return
+            }
+        }
+
+        self.join();
+        self.result()
+    }
+
+    fn observe(&mut self, events: Events<M>) {
+        events.adopt(&self.events);
+        self.events = events;
+    }
+
+    fn setup(&mut self) -> Result<(), MaridError> {
+        for idx in 0..self.runners.len() {
+            let events = self.member_events(idx);
+            self.runners[idx].observe(events);
+        }
+
+        match self.startup {
+            StartupMode::Sequential => {
+                let mut failed = None;
+                for idx in 0..self.runners.len() {
+                    let events = self.member_events(idx);
+                    if let Err(e) = setup_member(&mut self.runners[idx], &events) {
+                        failed = Some((idx, e));
+                        break
+                    }
+                }
+                if let Some((idx, e)) = failed {
+                    self.fail(idx, e);
+                    return self.result()
                 }
+            },
+            StartupMode::Parallel => {
+                let runners = mem::take(&mut self.runners);
+                let events = (0..runners.len()).map(|idx| self.member_events(idx)).collect();
+                match parallel_setup(runners, events) {
+                    Ok(runners) => self.runners = runners,
+                    Err((idx, e)) => {
+                        self.fail(idx, e);
+                        return self.result()
+                    },
+                }
+            },
+            StartupMode::Ordered => {
+                let runners = mem::take(&mut self.runners);
+                for (idx, mut r) in runners.into_iter().enumerate() {
+                    let events = self.member_events(idx);
+                    if let Err(e) = setup_member(&mut r, &events) {
+                        self.fail(idx, e);
+                        return self.abort_setup()
+                    }
+                    self.spawn(idx, r, false);
+                    if self.wait_ready(idx) {
+                        return self.abort_setup()
+                    }
+                }
+            },
+        }
+        self.state = State::SetupDone;
+        Ok(())
+    }
+}
+
+impl<R, M> Drop for Composer<R, M> {
+    // Refuses any command still queued or sent from now on.
+    fn drop(&mut self) {
+        *self.closed.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        let commands = match self.command_recv.take() {
+            Some(commands) => commands,
+            None => return,
+        };
+        let mut empty = false;
+        while !empty {
+            chan_select! {
+                default => empty = true,
+                commands.recv() -> cmd => empty = cmd.is_none(),
             }
         }
-    })
+    }
+}
+
+// Builds the error of a group of runners from the failures of its members. The first
+// failure triggered the teardown of the group, unless it was torn down by a signal.
+pub fn group_error(failures: Vec<MemberError>, triggered: bool) -> GroupError {
+    GroupError {
+        failures: failures,
+        triggered: triggered,
+    }
+}
+
+pub fn unknown_member(name: String) -> MaridError {
+    Box::new(MembershipError::UnknownMember(name))
+}
+
+// Sets up a member, emitting its setup events.
+pub fn setup_member<M>(runner: &mut Box<Runner<M> + Send>, events: &Events<M>) -> Result<(), MaridError>
+    where M: Clone + fmt::Debug {
+    events.emit(EventKind::SetupStarted);
+    let res = catch_runner(|| runner.setup());
+    events.emit(outcome_event(&res, EventKind::SetupFinished));
+    res
+}
+
+// Each runner is moved onto its own thread for setup and sent back once done. On the
+// first error the remaining threads are abandoned, their runners are dropped when they
+// finish.
+fn parallel_setup<M>(runners: Vec<Box<Runner<M> + Send>>, events: Vec<Events<M>>)
+    -> Result<Vec<Box<Runner<M> + Send>>, Failure> where M: Clone + fmt::Debug + Send + 'static {
+    let count = runners.len();
+    let (sn, rc) = mpsc::channel();
+    for ((idx, mut r), events) in runners.into_iter().enumerate().zip(events) {
+        let sn = sn.clone();
+        thread::spawn(move || {
+            let res = setup_member(&mut r, &events);
+            let _ = sn.send((idx, r, res));
+        });
+    }
+    drop(sn);
+
+    let mut slots: Vec<Option<Box<Runner<M> + Send>>> = (0..count).map(|_| None).collect();
+    for _ in 0..count {
+        let (idx, r, res) = match rc.recv() {
+            Ok(msg) => msg,
+            Err(_) => {
+                // A setup thread went away without reporting back.
+                let idx = slots.iter().position(|r| r.is_none()).unwrap_or(0);
+                let err = PanicError { message: "setup thread exited".to_string() };
+                return Err((idx, Box::new(err)))
+            },
+        };
+        try!(res.map_err(|e| (idx, e)));
+        slots[idx] = Some(r);
+    }
+    Ok(slots.into_iter().map(|r| r.expect("Runner was not set up")).collect())
 }
 
 #[cfg(test)]
 mod tests {
     use test_helpers::{TestRunner, TestError};
-    use {Composer, Runner, Signal, MaridError};
+    use {Composer, StartupMode, ShutdownPolicy, Route, EventKind, GroupError, MembershipError, PanicError, Escalation, Ready, Runner, Signal, Receiver, MaridError};
     use thunk::Thunk;
     use chan;
     use std::thread;
+    use std::sync::{Arc, Barrier, Mutex, mpsc};
+    use std::time::Duration;
+
+    type SetupFn = Thunk<'static, (), Result<(), MaridError>>;
+    type RunFn = Thunk<'static, Receiver<Signal>, Result<(), MaridError>>;
+
+    struct StepRunner {
+        setup: Option<SetupFn>,
+        run: RunFn,
+    }
+
+    impl StepRunner {
+        fn boxed<S, F>(setup: S, run: F) -> Box<Runner + Send>
+            where S: FnOnce() -> Result<(), MaridError> + Send + 'static,
+                  F: FnOnce(Receiver<Signal>) -> Result<(), MaridError> + Send + 'static {
+            Box::new(StepRunner{
+                setup: Some(Thunk::with_arg(move |()| setup())),
+                run: Thunk::with_arg(run),
+            })
+        }
+    }
+
+    impl Runner for StepRunner {
+        fn setup(&mut self) -> Result<(), MaridError> {
+            self.setup.take().expect("setup called twice").invoke(())
+        }
+
+        fn run(self: Box<Self>, signals: Receiver<Signal>) -> Result<(), MaridError> {
+            self.run.invoke(signals)
+        }
+    }
+
+    // Closes its Ready handle once released, then waits for a signal.
+    struct LateReady {
+        release: mpsc::Receiver<()>,
+    }
+
+    impl Runner for LateReady {
+        fn setup(&mut self) -> Result<(), MaridError> {
+            Ok(())
+        }
+
+        fn run(self: Box<Self>, signals: Receiver<Signal>) -> Result<(), MaridError> {
+            self.run_ready(signals, Ready::unobserved())
+        }
+
+        fn run_ready(self: Box<Self>, signals: Receiver<Signal>, ready: Ready) -> Result<(), MaridError> {
+            let _ = self.release.recv();
+            ready.close();
+            signals.recv();
+            Ok(())
+        }
+    }
+
+    fn ok_run(_signals: Receiver<Signal>) -> Result<(), MaridError> {
+        Ok(())
+    }
+
+    // Waits for a signal, then records that the runner has stopped.
+    fn stop_logger(id: usize, log: Arc<Mutex<Vec<usize>>>) -> RunFn {
+        Thunk::with_arg(move |signals: Receiver<Signal>| {
+            signals.recv().expect("Could not recv signal");
+            log.lock().unwrap().push(id);
+            Ok(())
+        })
+    }
 
     #[test]
     fn test_composer_runner() {
@@ -183,8 +1217,11 @@ mod tests {
             sig_send.send(Signal::HUP);
         });
 
-        let res = composer.run(signals);
-        assert!(res.is_err());
+        let err = composer.run(signals).err().expect("Expected an error");
+        let err = err.downcast_ref::<GroupError>().expect("Expected a GroupError");
+        let mut failed: Vec<usize> = err.failures().iter().map(|f| f.index).collect();
+        failed.sort();
+        assert_eq!(failed, vec!(0, 1));
 
         assert!(!rc.recv().expect("Did not recv"));
         assert!(!rc.recv().expect("Did not recv"));
@@ -205,8 +1242,11 @@ mod tests {
         let res = composer.setup();
         assert!(res.is_ok());
 
-        let res = composer.run(signals);
-        assert!(res.is_err());
+        let err = composer.run(signals).err().expect("Expected an error");
+        let err = err.downcast_ref::<GroupError>().expect("Expected a GroupError");
+        // runner1 is stopped with the error signal, which it handles cleanly.
+        assert_eq!(err.failures().len(), 1);
+        assert_eq!(err.trigger().expect("Expected a trigger").index, 1);
     }
 
     #[test]
@@ -229,4 +1269,428 @@ mod tests {
         assert!(rc.recv().expect("Did not recv"));
         assert!(rc.recv().expect("Did not recv"));
     }
+
+    #[test]
+    fn test_composer_parallel_setup() {
+        let barrier = Arc::new(Barrier::new(2));
+        let b1 = barrier.clone();
+        let b2 = barrier.clone();
+        // Each setup blocks until the other one has started.
+        let runner1 = StepRunner::boxed(move || { b1.wait(); Ok(()) }, ok_run);
+        let runner2 = StepRunner::boxed(move || { b2.wait(); Ok(()) }, ok_run);
+
+        let (_sig_send, signals) = chan::async();
+        let mut composer = Box::new(Composer::new(vec!(runner1, runner2), Signal::INT)
+                                    .startup(StartupMode::Parallel));
+        assert!(composer.setup().is_ok());
+        assert!(composer.run(signals).is_ok());
+    }
+
+    #[test]
+    fn test_composer_parallel_setup_fail_fast() {
+        let (release_sn, release_rc) = mpsc::channel::<()>();
+        let runner1 = StepRunner::boxed(move || { let _ = release_rc.recv(); Ok(()) }, ok_run);
+        let runner2 = StepRunner::boxed(|| Err(Box::new(TestError) as MaridError), ok_run);
+
+        let mut composer = Box::new(Composer::new(vec!(runner1, runner2), Signal::INT)
+                                    .startup(StartupMode::Parallel));
+        // runner1 is still blocked in setup at this point.
+        let err = composer.setup().err().expect("Expected a setup error");
+        let err = err.downcast_ref::<GroupError>().expect("Expected a GroupError");
+        assert_eq!(err.trigger().expect("Expected a trigger").index, 1);
+        drop(release_sn);
+    }
+
+    #[test]
+    fn test_composer_sequential_setup_error() {
+        let runner1 = StepRunner::boxed(|| Ok(()), ok_run);
+        let runner2 = StepRunner::boxed(|| Err(Box::new(TestError) as MaridError), ok_run);
+
+        let mut composer = Box::new(Composer::new(vec!(runner1, runner2), Signal::INT));
+        let err = composer.setup().err().expect("Expected a setup error");
+        let err = err.downcast_ref::<GroupError>().expect("Expected a GroupError");
+        assert_eq!(err.trigger().expect("Expected a trigger").index, 1);
+    }
+
+    #[test]
+    fn test_composer_ordered_startup() {
+        let log = Arc::new(Mutex::new(vec!()));
+        let (serving_sn, serving_rc) = mpsc::channel();
+
+        let stop0 = stop_logger(0, log.clone());
+        let runner0 = StepRunner::boxed(|| Ok(()), move |signals| {
+            serving_sn.send(()).unwrap();
+            stop0.invoke(signals)
+        });
+        // Only succeeds if runner0 is already running.
+        let stop1 = stop_logger(1, log.clone());
+        let runner1 = StepRunner::boxed(move || {
+            serving_rc.recv_timeout(Duration::from_secs(5))
+                .map_err(|_| Box::new(TestError) as MaridError)
+        }, move |signals| stop1.invoke(signals));
+
+        let (sig_send, signals) = chan::async();
+        let mut composer = Box::new(Composer::new(vec!(runner0, runner1), Signal::INT)
+                                    .startup(StartupMode::Ordered));
+        assert!(composer.setup().is_ok());
+
+        sig_send.send(Signal::INT);
+        assert!(composer.run(signals).is_ok());
+        assert_eq!(*log.lock().unwrap(), vec!(1, 0));
+    }
+
+    #[test]
+    fn test_composer_ordered_setup_error() {
+        let log = Arc::new(Mutex::new(vec!()));
+
+        let stop0 = stop_logger(0, log.clone());
+        let runner0 = StepRunner::boxed(|| Ok(()), move |signals| stop0.invoke(signals));
+        let runner1 = StepRunner::boxed(|| Err(Box::new(TestError) as MaridError), ok_run);
+
+        let mut composer = Box::new(Composer::new(vec!(runner0, runner1), Signal::INT)
+                                    .startup(StartupMode::Ordered));
+        let err = composer.setup().err().expect("Expected a setup error");
+        let err = err.downcast_ref::<GroupError>().expect("Expected a GroupError");
+        assert_eq!(err.trigger().expect("Expected a trigger").index, 1);
+        // The already running member has been stopped.
+        assert_eq!(*log.lock().unwrap(), vec!(0));
+    }
+
+    #[test]
+    fn test_composer_reverse_shutdown() {
+        let log = Arc::new(Mutex::new(vec!()));
+        let runners = (0..3).map(|id| {
+            let stop = stop_logger(id, log.clone());
+            StepRunner::boxed(|| Ok(()), move |signals| stop.invoke(signals))
+        }).collect();
+
+        let (sig_send, signals) = chan::async();
+        let composer = Box::new(Composer::new(runners, Signal::INT)
+                                .shutdown(ShutdownPolicy::Reverse(None)));
+
+        sig_send.send(Signal::INT);
+        assert!(composer.run(signals).is_ok());
+        assert_eq!(*log.lock().unwrap(), vec!(2, 1, 0));
+    }
+
+    #[test]
+    fn test_composer_reverse_shutdown_grace_period() {
+        let log = Arc::new(Mutex::new(vec!()));
+        let (release_sn, release_rc) = mpsc::channel();
+
+        // runner0 is only signaled once runner1's grace period has elapsed, and then
+        // lets runner1 finish.
+        let stop0 = stop_logger(0, log.clone());
+        let runner0 = StepRunner::boxed(|| Ok(()), move |signals| {
+            let res = stop0.invoke(signals);
+            release_sn.send(()).unwrap();
+            res
+        });
+        let log1 = log.clone();
+        let runner1 = StepRunner::boxed(|| Ok(()), move |signals: Receiver<Signal>| {
+            signals.recv().expect("Could not recv signal");
+            release_rc.recv().unwrap();
+            log1.lock().unwrap().push(1);
+            Ok(())
+        });
+
+        let (sig_send, signals) = chan::async();
+        let composer = Box::new(Composer::new(vec!(runner0, runner1), Signal::INT)
+                                .shutdown(ShutdownPolicy::Reverse(Some(Duration::from_millis(50)))));
+
+        sig_send.send(Signal::INT);
+        assert!(composer.run(signals).is_ok());
+        assert_eq!(*log.lock().unwrap(), vec!(0, 1));
+    }
+
+    #[test]
+    fn test_composer_ordered_non_shutdown_signal() {
+        let first = Arc::new(Mutex::new(vec!()));
+        let second = Arc::new(Mutex::new(vec!()));
+        let (sig_send, signals) = chan::async();
+        let composer = Box::new(Composer::new(vec!(recorder(first.clone()), recorder(second.clone())),
+                                              Signal::INT)
+                                .startup(StartupMode::Ordered));
+
+        // HUP reaches both members without waiting for either of them to exit.
+        sig_send.send(Signal::HUP);
+        sig_send.send(Signal::INT);
+        assert!(composer.run(signals).is_ok());
+        assert_eq!(*first.lock().unwrap(), vec!(Signal::HUP, Signal::INT));
+        assert_eq!(*second.lock().unwrap(), vec!(Signal::HUP, Signal::INT));
+    }
+
+    #[test]
+    fn test_composer_named_member_error() {
+        let runner1 = StepRunner::boxed(|| Ok(()), ok_run);
+        let runner2 = StepRunner::boxed(|| Err(Box::new(TestError) as MaridError), ok_run);
+
+        let mut composer = Box::new(Composer::new(vec!(runner1), Signal::INT)
+                                    .member("cache", runner2));
+        let err = composer.setup().err().expect("Expected a setup error");
+        assert_eq!(format!("{}", err), "1 member(s) failed: member cache (1): a testing error");
+    }
+
+    #[test]
+    fn test_composer_member_panic() {
+        let (sn, rc) = chan::sync(1);
+        let runner1 = Box::new(TestRunner::new(1, sn)) as Box<Runner + Send>;
+        let runner2 = StepRunner::boxed(|| Ok(()), |_signals| panic!("member exploded"));
+
+        let (_sig_send, signals) = chan::async();
+        let composer = Box::new(Composer::new(vec!(runner1, runner2), Signal::INT));
+
+        let err = composer.run(signals).err().expect("Expected an error");
+        let err = err.downcast_ref::<GroupError>().expect("Expected a GroupError");
+        assert_eq!(err.failures().len(), 1);
+        assert_eq!(err.trigger().expect("Expected a trigger").index, 1);
+        let panic = err.trigger().unwrap().error.downcast_ref::<PanicError>().expect("Expected a PanicError");
+        assert_eq!(panic.message, "member exploded");
+        // The other member was torn down cleanly.
+        assert!(rc.recv().expect("Did not recv"));
+    }
+
+    #[test]
+    fn test_composer_escalation() {
+        let (_release_sn, release_rc) = mpsc::channel::<()>();
+        // Only exits once it receives the force signal.
+        let runner1 = StepRunner::boxed(|| Ok(()), |signals: Receiver<Signal>| {
+            while signals.recv() != Some(Signal::KILL) {}
+            Ok(())
+        });
+        // Never exits while the test is running.
+        let runner2 = StepRunner::boxed(|| Ok(()), move |_signals| {
+            let _ = release_rc.recv();
+            Ok(())
+        });
+
+        let (sig_send, signals) = chan::async();
+        let escalation = Escalation::new(Signal::INT, Duration::from_millis(20),
+                                         Signal::KILL, Duration::from_millis(20));
+        let composer = Box::new(Composer::new(vec!(runner1), Signal::INT)
+                                .member("stuck", runner2)
+                                .escalate(escalation));
+
+        sig_send.send(Signal::INT);
+        let err = composer.run(signals).err().expect("Expected an error");
+        let err = err.downcast_ref::<GroupError>().expect("Expected a GroupError");
+        assert_eq!(err.failures().len(), 1);
+        // The group was torn down by its shutdown signal.
+        assert!(err.trigger().is_none());
+        let stuck = err.still_running();
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].name, Some("stuck".to_string()));
+    }
+
+    #[test]
+    fn test_composer_ordered_escalation() {
+        let log = Arc::new(Mutex::new(vec!()));
+        let stop0 = stop_logger(0, log.clone());
+        let runner0 = StepRunner::boxed(|| Ok(()), move |signals| stop0.invoke(signals));
+        // Only exits once it receives the force signal.
+        let log1 = log.clone();
+        let runner1 = StepRunner::boxed(|| Ok(()), move |signals: Receiver<Signal>| {
+            while signals.recv() != Some(Signal::KILL) {}
+            log1.lock().unwrap().push(1);
+            Ok(())
+        });
+
+        let (sig_send, signals) = chan::async();
+        let escalation = Escalation::new(Signal::INT, Duration::from_millis(20),
+                                         Signal::KILL, Duration::from_secs(5));
+        let composer = Box::new(Composer::new(vec!(runner0, runner1), Signal::INT)
+                                .startup(StartupMode::Ordered)
+                                .escalate(escalation));
+
+        // runner0 is stopped once runner1's grace period has elapsed.
+        sig_send.send(Signal::INT);
+        assert!(composer.run(signals).is_ok());
+        assert_eq!(*log.lock().unwrap(), vec!(0, 1));
+    }
+
+    #[test]
+    fn test_composer_dynamic_members() {
+        let log = Arc::new(Mutex::new(vec!()));
+        let mut composer = Box::new(Composer::new(vec!(), Signal::INT));
+        let handle = composer.handle();
+
+        let (sig_send, signals) = chan::async();
+        let group = thread::spawn(move || composer.run(signals));
+
+        let (exited_sn, exited_rc) = mpsc::channel();
+        let failing = StepRunner::boxed(|| Ok(()), move |signals: Receiver<Signal>| {
+            signals.recv().expect("Could not recv signal");
+            exited_sn.send(()).unwrap();
+            Err(Box::new(TestError) as MaridError)
+        });
+        assert!(handle.add("failing", failing).is_ok());
+        let stop = stop_logger(1, log.clone());
+        let logger = StepRunner::boxed(|| Ok(()), move |signals| stop.invoke(signals));
+        assert!(handle.add("logger", logger).is_ok());
+
+        let duplicate = StepRunner::boxed(|| Ok(()), ok_run);
+        let err = handle.add("logger", duplicate).err().expect("Expected an error");
+        assert_eq!(err.downcast_ref::<MembershipError>(),
+                   Some(&MembershipError::DuplicateName("logger".to_string())));
+        let bad_setup = StepRunner::boxed(|| Err(Box::new(TestError) as MaridError), ok_run);
+        assert!(handle.add("bad", bad_setup).err().expect("Expected an error").is::<TestError>());
+
+        // The removed member's error does not tear down the group, and it has exited
+        // once remove returns.
+        assert!(handle.remove("failing", Signal::HUP).is_ok());
+        assert!(exited_rc.try_recv().is_ok());
+        let err = handle.remove("failing", Signal::HUP).err().expect("Expected an error");
+        assert_eq!(err.downcast_ref::<MembershipError>(),
+                   Some(&MembershipError::UnknownMember("failing".to_string())));
+        sig_send.send(Signal::INT);
+        assert!(group.join().unwrap().is_ok());
+        assert_eq!(*log.lock().unwrap(), vec!(1));
+
+        let late = StepRunner::boxed(|| Ok(()), ok_run);
+        let err = handle.add("late", late).err().expect("Expected an error");
+        assert_eq!(err.downcast_ref::<MembershipError>(), Some(&MembershipError::Closed));
+    }
+
+    #[test]
+    fn test_composer_dynamic_setup() {
+        let mut composer = Box::new(Composer::new(vec!(), Signal::INT));
+        let handle = composer.handle();
+        let events = composer.subscribe();
+        let (sig_send, signals) = chan::async();
+        let group = thread::spawn(move || composer.run(signals));
+
+        // A signal other than the error_signal does not close the group.
+        sig_send.send(Signal::HUP);
+
+        let (release_sn, release_rc) = mpsc::channel::<()>();
+        let slow = StepRunner::boxed(move || { release_rc.recv().unwrap(); Ok(()) },
+                                     |signals: Receiver<Signal>| { signals.recv(); Ok(()) });
+        let slow_handle = handle.clone();
+        let adding = thread::spawn(move || slow_handle.add("slow", slow));
+
+        // The group keeps handling commands while "slow" is being setup, and forgets
+        // the members that have exited.
+        for _ in 0..3 {
+            assert!(handle.add("once", StepRunner::boxed(|| Ok(()), ok_run)).is_ok());
+            while events.recv().unwrap().kind != EventKind::Exited(Ok(())) {}
+        }
+        let err = handle.add("slow", StepRunner::boxed(|| Ok(()), ok_run)).err().expect("Expected an error");
+        assert_eq!(err.downcast_ref::<MembershipError>(),
+                   Some(&MembershipError::DuplicateName("slow".to_string())));
+
+        release_sn.send(()).unwrap();
+        assert!(adding.join().unwrap().is_ok());
+        assert!(handle.signal("slow", Signal::HUP).is_ok());
+        sig_send.send(Signal::INT);
+        assert!(group.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_composer_handles_dropped() {
+        let mut composer = Box::new(Composer::new(vec!(), Signal::INT));
+        let handle = composer.handle();
+        let (_sig_send, signals) = chan::async();
+        drop(handle);
+        assert!(composer.run(signals).is_ok());
+    }
+
+    #[test]
+    fn test_composer_ordered_waits_for_ready() {
+        let (release_sn, release_rc) = mpsc::channel();
+        let (setup_sn, setup_rc) = mpsc::channel();
+        let runner0 = Box::new(LateReady { release: release_rc }) as Box<Runner + Send>;
+        let runner1 = StepRunner::boxed(move || { setup_sn.send(()).unwrap(); Ok(()) },
+                                        |signals: Receiver<Signal>| { signals.recv(); Ok(()) });
+
+        let (ready_sn, ready_rc) = mpsc::channel();
+        let ready = Ready::new(move || ready_sn.send(()).unwrap());
+        let (sig_send, signals) = chan::async();
+        let composer = Box::new(Composer::new(vec!(runner0, runner1), Signal::INT)
+                                .startup(StartupMode::Ordered));
+        let group = thread::spawn(move || composer.run_ready(signals, ready));
+
+        // runner1 is not setup until runner0 has reported ready.
+        assert!(setup_rc.recv_timeout(Duration::from_millis(20)).is_err());
+        assert!(ready_rc.try_recv().is_err());
+        release_sn.send(()).unwrap();
+        setup_rc.recv().unwrap();
+        ready_rc.recv().unwrap();
+
+        sig_send.send(Signal::INT);
+        assert!(group.join().unwrap().is_ok());
+    }
+
+    // Records every signal received until INT.
+    fn recorder(log: Arc<Mutex<Vec<Signal>>>) -> Box<Runner + Send> {
+        StepRunner::boxed(|| Ok(()), move |signals: Receiver<Signal>| {
+            while let Some(sig) = signals.recv() {
+                log.lock().unwrap().push(sig);
+                if sig == Signal::INT {
+                    break
+                }
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_route_apply() {
+        let route = Route::new().only(vec!(Signal::HUP, Signal::TERM)).map(Signal::INT, Signal::TERM);
+        assert_eq!(route.apply(&Signal::HUP), Some(Signal::HUP));
+        assert_eq!(route.apply(&Signal::INT), Some(Signal::TERM));
+        assert_eq!(route.apply(&Signal::USR1), None);
+        assert_eq!(Route::new().ignore(Signal::HUP).apply(&Signal::HUP), None);
+    }
+
+    #[test]
+    fn test_composer_routes() {
+        let reloads = Arc::new(Mutex::new(vec!()));
+        let work = Arc::new(Mutex::new(vec!()));
+        let (sig_send, signals) = chan::async();
+        let composer = Box::new(Composer::new(vec!(), Signal::INT)
+                                .member("reloader", recorder(reloads.clone()))
+                                .member("workers", recorder(work.clone()))
+                                .route("reloader", Route::new().map(Signal::HUP, Signal::USR1))
+                                .route("workers", Route::new().ignore(Signal::HUP)));
+
+        sig_send.send(Signal::HUP);
+        sig_send.send(Signal::INT);
+        assert!(composer.run(signals).is_ok());
+        assert_eq!(*reloads.lock().unwrap(), vec!(Signal::USR1, Signal::INT));
+        assert_eq!(*work.lock().unwrap(), vec!(Signal::INT));
+    }
+
+    #[test]
+    fn test_composer_events() {
+        let (sn, _rc) = chan::sync(1);
+        let runner0 = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+        let runner1 = StepRunner::boxed(|| Ok(()), |_signals| panic!("boom"));
+        let composer = Box::new(Composer::new(vec!(), Signal::INT)
+                                .member("test", runner0)
+                                .member("panics", runner1));
+        let events = composer.subscribe();
+
+        let (_sig_send, signals) = chan::async();
+        assert!(composer.run(signals).is_err());
+
+        let mut kinds = vec!(vec!(), vec!());
+        for event in events.try_iter() {
+            let idx = event.index.expect("Event without a member index");
+            assert_eq!(event.name.as_ref().map(|n| &n[..]), Some(["test", "panics"][idx]));
+            kinds[idx].push(event.kind);
+        }
+        // The first member may be signaled before its thread reports it ready.
+        assert!(kinds[0].contains(&EventKind::Ready));
+        kinds[0].retain(|k| *k != EventKind::Ready);
+        assert_eq!(kinds[0], vec!(EventKind::SetupStarted,
+                                  EventKind::SetupFinished(Ok(())),
+                                  EventKind::Running,
+                                  EventKind::SignalDelivered(Signal::INT),
+                                  EventKind::Exited(Ok(()))));
+        assert_eq!(kinds[1], vec!(EventKind::SetupStarted,
+                                  EventKind::SetupFinished(Ok(())),
+                                  EventKind::Running,
+                                  EventKind::Ready,
+                                  EventKind::Panicked("boom".to_string())));
+    }
 }
@@ -0,0 +1,467 @@
+use traits::{Runner, Signal, Receiver};
+use ready::Ready;
+use events::{Event, Events};
+use composer::{GroupHandle, MembershipError};
+use supervisor::SupervisorHandle;
+use tree::SharedTree;
+use {MaridError};
+use std::fmt;
+use std::fs;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+// How long `restart` waits for the member to exit before giving up.
+const RESTART_TIMEOUT_MS: u64 = 10000;
+
+type Factory<M> = Box<FnMut() -> Box<Runner<M> + Send> + Send>;
+
+// The runners a server applies its commands to.
+enum Group<M> {
+    Composer(GroupHandle<M>),
+    Supervisor(SupervisorHandle<M>),
+}
+
+impl<M> Clone for Group<M> {
+    fn clone(&self) -> Group<M> {
+        match *self {
+            Group::Composer(ref handle) => Group::Composer(handle.clone()),
+            Group::Supervisor(ref handle) => Group::Supervisor(handle.clone()),
+        }
+    }
+}
+
+/// A Runner accepting commands for the members of its Composer, or the children of its
+/// Supervisor, on a Unix socket.
+///
+/// Clients send one command per line, and each reply ends with a line reading `ok` or
+/// starting with `error:`. The commands are:
+///
+/// * `status` replies whether every runner of the tree is ready.
+/// * `tree` replies with a line per runner of the tree, giving its path from the root,
+///   such as `inner/worker`, and its state.
+/// * `signal <member> <SIG>` sends a signal, such as `HUP` or `SIGHUP`, to the member.
+/// * `stop <member>` removes the member from the group, sending it the stop signal, and
+///   replies once it has exited. A child of a Supervisor is stopped, and kept stopped
+///   until it is restarted.
+/// * `restart <member>` stops the member if it is running, waits for it to exit, and
+///   adds a runner made by the factory given to `restartable()` under the same name. A
+///   child of a Supervisor is restarted with a runner made by its ChildSpec instead.
+///
+/// Members are those of the server's own group, named as given to it, while `status`
+/// and `tree` cover the whole tree.
+///
+/// The server is given a GroupHandle of the Composer it is a member of, or with
+/// `bind_supervisor()` a SupervisorHandle, and follows the lifecycle events of the
+/// whole tree. Each connection is served on its own thread, and
+/// a `restart` only holds back the connection that sent it.
+///
+/// The server exits once it receives its shutdown signal, ignoring any other signal, and
+/// removes the socket.
+pub struct ControlServer<M = Signal> {
+    listener: UnixListener,
+    path: PathBuf,
+    group: Group<M>,
+    shutdown_signal: M,
+    stop_signal: M,
+    factories: HashMap<String, Factory<M>>,
+    events: Option<mpsc::Receiver<Event<M>>>,
+}
+
+impl<M> ControlServer<M> where M: From<Signal> + Clone + PartialEq + fmt::Debug + Send + 'static {
+    /// Creates a server listening on the socket at the given path, which exits once it
+    /// receives the shutdown_signal.
+    ///
+    /// A socket left at the path by a server that did not exit cleanly is removed first.
+    /// Binding fails if a server still accepts connections on it, or if the path is not a
+    /// socket. By default, `stop` and `restart` send `Signal::TERM`.
+    pub fn bind<P: AsRef<Path>>(path: P, group: GroupHandle<M>, shutdown_signal: M)
+        -> io::Result<ControlServer<M>> {
+        ControlServer::listen(path.as_ref(), Group::Composer(group), shutdown_signal)
+    }
+
+    /// Like `bind()`, for a server applying its commands to the children of a Supervisor.
+    pub fn bind_supervisor<P: AsRef<Path>>(path: P, supervisor: SupervisorHandle<M>, shutdown_signal: M)
+        -> io::Result<ControlServer<M>> {
+        ControlServer::listen(path.as_ref(), Group::Supervisor(supervisor), shutdown_signal)
+    }
+
+    fn listen(path: &Path, group: Group<M>, shutdown_signal: M) -> io::Result<ControlServer<M>> {
+        try!(unlink_stale(path));
+        let listener = try!(UnixListener::bind(&path));
+        Ok(ControlServer {
+            listener: listener,
+            path: path.to_path_buf(),
+            group: group,
+            shutdown_signal: shutdown_signal,
+            stop_signal: M::from(Signal::TERM),
+            factories: HashMap::new(),
+            events: None,
+        })
+    }
+
+    /// Sets the signal sent to members by `stop` and `restart`.
+    pub fn stop_signal(mut self, signal: M) -> ControlServer<M> {
+        self.stop_signal = signal;
+        self
+    }
+
+    /// Allows the named member of a Composer to be restarted, with runners made by the
+    /// factory.
+    pub fn restartable<F>(mut self, name: &str, factory: F) -> ControlServer<M>
+        where F: FnMut() -> Box<Runner<M> + Send> + Send + 'static {
+        self.factories.insert(name.to_string(), Box::new(factory));
+        self
+    }
+}
+
+// Removes a socket left behind by a server that did not exit cleanly. A socket that
+// still accepts connections is left alone, so that binding it fails.
+fn unlink_stale(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(()),
+    };
+    if metadata.file_type().is_socket() && UnixStream::connect(path).is_err() {
+        try!(fs::remove_file(path));
+    }
+    Ok(())
+}
+
+// The state of a server, cloned for each of its connections. Locks are only held to
+// read the tree or to build a runner, never while waiting on a member.
+struct Control<M> {
+    group: Group<M>,
+    stop_signal: M,
+    factories: Arc<Mutex<HashMap<String, Factory<M>>>>,
+    tree: Arc<SharedTree>,
+}
+
+impl<M> Clone for Control<M> where M: Clone {
+    fn clone(&self) -> Control<M> {
+        Control {
+            group: self.group.clone(),
+            stop_signal: self.stop_signal.clone(),
+            factories: self.factories.clone(),
+            tree: self.tree.clone(),
+        }
+    }
+}
+
+impl<M> Control<M> where M: From<Signal> + Clone + Send + 'static {
+    // Applies a command, returning the lines of its reply.
+    fn apply(&self, line: &str) -> Result<Vec<String>, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match &words[..] {
+            ["status"] => {
+                let status = if self.tree.lock().ready() { "ready" } else { "not ready" };
+                Ok(vec!(status.to_string()))
+            },
+            ["tree"] => Ok(self.tree.lock().lines()),
+            ["signal", member, sig] => {
+                let sig = try!(parse_signal(sig).ok_or_else(|| format!("unknown signal: {}", sig)));
+                let res = match self.group {
+                    Group::Composer(ref group) => group.signal(member, M::from(sig)),
+                    Group::Supervisor(ref supervisor) => supervisor.signal(member, M::from(sig)),
+                };
+                try!(res.map_err(|e| e.to_string()));
+                Ok(vec!())
+            },
+            ["stop", member] => {
+                let res = match self.group {
+                    Group::Composer(ref group) => group.remove(member, self.stop_signal.clone()),
+                    Group::Supervisor(ref supervisor) => supervisor.stop(member, self.stop_signal.clone()),
+                };
+                try!(res.map_err(|e| e.to_string()));
+                Ok(vec!())
+            },
+            ["restart", member] => self.restart(member),
+            [] => Err("empty command".to_string()),
+            _ => Err(format!("unknown command: {}", line.trim())),
+        }
+    }
+
+    fn restart(&self, member: &str) -> Result<Vec<String>, String> {
+        let group = match self.group {
+            Group::Composer(ref group) => group,
+            Group::Supervisor(ref supervisor) => {
+                try!(supervisor.restart(member, self.stop_signal.clone()).map_err(|e| e.to_string()));
+                return Ok(vec!())
+            },
+        };
+        if !self.factories.lock().unwrap().contains_key(member) {
+            return Err(format!("{} cannot be restarted", member))
+        }
+        // The group replies once the member has exited, or that it has no such member.
+        let (sn, rc) = mpsc::channel();
+        let (handle, name, signal) = (group.clone(), member.to_string(), self.stop_signal.clone());
+        thread::spawn(move || {
+            let _ = sn.send(handle.remove(&name, signal));
+        });
+        match rc.recv_timeout(Duration::from_millis(RESTART_TIMEOUT_MS)) {
+            Ok(Ok(())) => {},
+            // A member that is not running is simply added.
+            Ok(Err(e)) => match e.downcast_ref::<MembershipError>() {
+                Some(&MembershipError::UnknownMember(_)) => {},
+                _ => return Err(e.to_string()),
+            },
+            Err(_) => return Err(format!("{} is still running", member)),
+        }
+        let runner = {
+            let mut factories = self.factories.lock().unwrap();
+            (factories.get_mut(member).expect("Factory missing"))()
+        };
+        try!(group.add(member, runner).map_err(|e| e.to_string()));
+        Ok(vec!())
+    }
+}
+
+// Names a signal, with or without its SIG prefix.
+fn parse_signal(name: &str) -> Option<Signal> {
+    let name = name.trim_start_matches("SIG");
+    let signals = [
+        ("HUP", Signal::HUP), ("INT", Signal::INT), ("QUIT", Signal::QUIT),
+        ("ILL", Signal::ILL), ("ABRT", Signal::ABRT), ("FPE", Signal::FPE),
+        ("KILL", Signal::KILL), ("SEGV", Signal::SEGV), ("PIPE", Signal::PIPE),
+        ("ALRM", Signal::ALRM), ("TERM", Signal::TERM), ("USR1", Signal::USR1),
+        ("USR2", Signal::USR2), ("CHLD", Signal::CHLD), ("CONT", Signal::CONT),
+        ("STOP", Signal::STOP), ("TSTP", Signal::TSTP), ("TTIN", Signal::TTIN),
+        ("TTOU", Signal::TTOU), ("BUS", Signal::BUS), ("PROF", Signal::PROF),
+        ("SYS", Signal::SYS), ("TRAP", Signal::TRAP), ("URG", Signal::URG),
+        ("VTALRM", Signal::VTALRM), ("XCPU", Signal::XCPU), ("XFSZ", Signal::XFSZ),
+        ("IO", Signal::IO), ("WINCH", Signal::WINCH),
+    ];
+    signals.iter().find(|s| s.0 == name).map(|s| s.1)
+}
+
+fn serve<M>(stream: UnixStream, control: Control<M>) -> io::Result<()>
+    where M: From<Signal> + Clone + Send + 'static {
+    let reader = BufReader::new(try!(stream.try_clone()));
+    let mut stream = stream;
+    for line in reader.lines() {
+        let line = try!(line);
+        let reply = control.apply(&line);
+        match reply {
+            Ok(lines) => {
+                for l in lines {
+                    try!(writeln!(stream, "{}", l));
+                }
+                try!(writeln!(stream, "ok"));
+            },
+            Err(e) => try!(writeln!(stream, "error: {}", e)),
+        }
+    }
+    Ok(())
+}
+
+impl<M> Runner<M> for ControlServer<M> where M: From<Signal> + Clone + PartialEq + fmt::Debug + Send + 'static {
+    fn observe(&mut self, events: Events<M>) {
+        self.events = Some(events.subscribe());
+    }
+
+    fn setup(&mut self) -> Result<(), MaridError> {
+        Ok(())
+    }
+
+    fn run(self: Box<Self>, signals: Receiver<M>) -> Result<(), MaridError> {
+        self.run_ready(signals, Ready::unobserved())
+    }
+
+    fn run_ready(self: Box<Self>, signals: Receiver<M>, ready: Ready) -> Result<(), MaridError> {
+        let server = *self;
+        let control = Control {
+            group: server.group,
+            stop_signal: server.stop_signal,
+            factories: Arc::new(Mutex::new(server.factories)),
+            tree: Arc::new(SharedTree::new(vec!())),
+        };
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let follower = server.events.map(|events| {
+            let tree = control.tree.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || tree.follow(events, &stopped))
+        });
+
+        let listener = server.listener;
+        let accept_stopped = stopped.clone();
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_stopped.load(Ordering::SeqCst) {
+                    break
+                }
+                if let Ok(stream) = stream {
+                    let control = control.clone();
+                    thread::spawn(move || serve(stream, control));
+                }
+            }
+        });
+        ready.close();
+
+        while let Some(sig) = signals.recv() {
+            if sig == server.shutdown_signal {
+                break
+            }
+        }
+        stopped.store(true, Ordering::SeqCst);
+        // Wakes up the accepting thread, which finds the server stopped.
+        let _ = UnixStream::connect(&server.path);
+        let _ = handle.join();
+        if let Some(follower) = follower {
+            let _ = follower.join();
+        }
+        let _ = fs::remove_file(&server.path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ControlServer, parse_signal};
+    use traits::{Runner, Process, Signal};
+    use composer::Composer;
+    use supervisor::{Supervisor, ChildSpec, RestartType, Strategy};
+    use process::MaridProcess;
+    use test_helpers::TestRunner;
+    use std::env;
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::process;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use chan;
+
+    // Sends a command, returning the lines of its reply.
+    fn command(stream: &mut BufReader<UnixStream>, line: &str) -> Vec<String> {
+        writeln!(stream.get_mut(), "{}", line).unwrap();
+        let mut reply = vec!();
+        loop {
+            let mut l = String::new();
+            stream.read_line(&mut l).unwrap();
+            let l = l.trim_end().to_string();
+            let done = l == "ok" || l.starts_with("error:");
+            reply.push(l);
+            if done {
+                return reply
+            }
+        }
+    }
+
+    #[test]
+    fn test_control_server() {
+        let path = env::temp_dir().join(format!("marid-control-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let (sn, rc) = chan::sync(1);
+        let worker = Box::new(TestRunner::new(0, sn.clone())) as Box<Runner + Send>;
+        let mut composer = Composer::new(vec!(), Signal::INT);
+        let server = ControlServer::bind(&path, composer.handle(), Signal::INT).unwrap()
+            .stop_signal(Signal::INT)
+            .restartable("worker", move || Box::new(TestRunner::new(0, sn.clone())) as Box<Runner + Send>);
+        // A member of a nested group shares the worker's name.
+        let (inner_sn, inner_rc) = chan::sync(1);
+        let inner = Composer::new(vec!(), Signal::INT)
+            .member("worker", Box::new(TestRunner::new(0, inner_sn)) as Box<Runner + Send>);
+        let composer = composer.member("worker", worker)
+            .member("inner", Box::new(inner))
+            .member("control", Box::new(server));
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(Box::new(composer), signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+
+        let mut client = BufReader::new(UnixStream::connect(&path).unwrap());
+        // Waits for the server to follow the events of the worker.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !command(&mut client, "tree").contains(&"inner/worker ready".to_string()) {
+            assert!(Instant::now() < deadline, "Worker is not ready");
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(command(&mut client, "status"), vec!("ready", "ok"));
+
+        // Only the shutdown signal stops the server.
+        assert_eq!(command(&mut client, "signal control HUP"), vec!("ok"));
+
+        assert!(command(&mut client, "tree").contains(&"worker ready".to_string()));
+
+        // Only the worker of the server's own group is restarted.
+        assert_eq!(command(&mut client, "restart worker"), vec!("ok"));
+        assert_eq!(rc.recv(), Some(true)); // Stopped with INT
+        assert_eq!(command(&mut client, "stop nobody"),
+                   vec!("error: no running member named nobody"));
+        assert_eq!(command(&mut client, "restart control"),
+                   vec!("error: control cannot be restarted"));
+        assert_eq!(command(&mut client, "jump"), vec!("error: unknown command: jump"));
+        assert_eq!(command(&mut client, "signal worker SIGHUP"), vec!("ok"));
+        assert_eq!(rc.recv(), Some(false)); // The new runner exits with an error
+
+        process.signal(Signal::INT);
+        assert!(process.wait().is_err());
+        assert_eq!(inner_rc.recv(), Some(true));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_control_server_supervisor() {
+        let path = env::temp_dir().join(format!("marid-supervisor-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let (sn, rc) = chan::sync(1);
+        let supervisor = Supervisor::new(Strategy::OneForOne, Signal::INT)
+            .child(ChildSpec::new("worker", RestartType::Permanent, move || {
+                Box::new(TestRunner::new(0, sn.clone())) as Box<Runner + Send>
+            }));
+        let handle = supervisor.handle();
+        let server_path = path.clone();
+        let supervisor = supervisor.child(ChildSpec::new("control", RestartType::Permanent, move || {
+            Box::new(ControlServer::bind_supervisor(&server_path, handle.clone(), Signal::INT).unwrap()
+                     .stop_signal(Signal::INT)) as Box<Runner + Send>
+        }));
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(Box::new(supervisor), signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+
+        let mut client = BufReader::new(UnixStream::connect(&path).unwrap());
+        assert_eq!(command(&mut client, "stop worker"), vec!("ok"));
+        assert_eq!(rc.recv(), Some(true)); // Stopped with INT
+        assert_eq!(command(&mut client, "signal worker HUP"),
+                   vec!("error: no running member named worker"));
+        assert_eq!(command(&mut client, "restart worker"), vec!("ok"));
+        assert_eq!(command(&mut client, "restart worker"), vec!("ok"));
+        assert_eq!(rc.recv(), Some(true)); // The restarted runner stopped with INT
+
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_control_server_stale_socket() {
+        let path = env::temp_dir().join(format!("marid-stale-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let mut composer = Composer::<Box<Runner + Send>>::new(vec!(), Signal::INT);
+
+        // A socket nobody listens on any longer is replaced.
+        drop(UnixListener::bind(&path).unwrap());
+        let server = ControlServer::bind(&path, composer.handle(), Signal::INT).unwrap();
+        // One that is still listened on is left alone.
+        assert!(ControlServer::bind(&path, composer.handle(), Signal::INT).is_err());
+        drop(server);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("TERM"), Some(Signal::TERM));
+        assert_eq!(parse_signal("SIGUSR1"), Some(Signal::USR1));
+        assert_eq!(parse_signal("usr1"), None);
+    }
+}
@@ -0,0 +1,231 @@
+use traits::Signal;
+use panic::PanicError;
+use logging::{self, Label};
+use metrics::Metrics;
+use {MaridError};
+use std::fmt;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::SystemTime;
+
+/// What happened to a runner.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum EventKind<M = Signal> {
+    /// The runner's setup() was called.
+    SetupStarted,
+    /// The runner's setup() returned. A failure is described by the error's message.
+    SetupFinished(Result<(), String>),
+    /// The runner's run() was called.
+    Running,
+    /// The runner reported that it is ready.
+    Ready,
+    /// The signal was sent to the runner.
+    SignalDelivered(M),
+    /// The runner's run() returned. A failure is described by the error's message.
+    Exited(Result<(), String>),
+    /// The runner was replaced by a new one after exiting.
+    Restarted,
+    /// The runner panicked during setup or while running, with the enclosed message.
+    Panicked(String),
+    /// The member has exited and was removed from its group, through a GroupHandle or
+    /// a SupervisorHandle, or forgotten after being added through a GroupHandle. Its
+    /// members, if any, are gone along with it.
+    Removed,
+}
+
+/// A lifecycle event of a runner.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Event<M = Signal> {
+    /// Index of the member the event is about, or None for the runner started by a
+    /// MaridProcess.
+    pub index: Option<usize>,
+    /// Name of the member the event is about, if it was given one.
+    pub name: Option<String>,
+    /// Labels of the members enclosing the runner, outermost first, such as the name of
+    /// a Composer that is itself a member. Empty for the members of the root runner.
+    pub parents: Vec<String>,
+    /// What happened.
+    pub kind: EventKind<M>,
+    /// When it happened.
+    pub time: SystemTime,
+}
+
+/// The lifecycle events of a runner, and of its members.
+///
+/// An Events handle is given to every runner through `Runner::observe()`. Events are
+/// emitted by the MaridProcess, Composer or Supervisor running the runner, which tag
+/// them with the index and name of the member, and with the labels of the members
+/// enclosing it. Clones share the same subscribers.
+///
+/// Events are also recorded into the Metrics registry of the handle.
+pub struct Events<M = Signal> {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<Event<M>>>>>,
+    registries: Arc<Mutex<Vec<Metrics>>>,
+    index: Option<usize>,
+    name: Option<String>,
+    parents: Vec<String>,
+}
+
+impl<M> Events<M> {
+    // Creates an Events handle without any subscriber.
+    pub(crate) fn new() -> Events<M> {
+        Events {
+            subscribers: Arc::new(Mutex::new(vec!())),
+            registries: Arc::new(Mutex::new(vec!())),
+            index: None,
+            name: None,
+            parents: vec!(),
+        }
+    }
+
+    /// Returns a Receiver of every event emitted from now on.
+    pub fn subscribe(&self) -> mpsc::Receiver<Event<M>> {
+        let (sn, rc) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sn);
+        rc
+    }
+
+    /// Returns the Metrics registry recording every event emitted from now on, creating
+    /// it on first use.
+    pub fn metrics(&self) -> Metrics {
+        let mut registries = self.registries.lock().unwrap();
+        if registries.is_empty() {
+            registries.push(Metrics::new());
+        }
+        registries[0].clone()
+    }
+
+    // Returns an Events handle emitting to the same subscribers, tagging events with
+    // the member's index and name. If this handle is itself a member's, that member
+    // becomes one of the parents of the new one.
+    pub(crate) fn member(&self, index: usize, name: Option<&str>) -> Events<M> {
+        let mut parents = self.parents.clone();
+        if self.index.is_some() {
+            parents.push(label(self).to_string());
+        }
+        Events {
+            subscribers: self.subscribers.clone(),
+            registries: self.registries.clone(),
+            index: Some(index),
+            name: name.map(|n| n.to_string()),
+            parents: parents,
+        }
+    }
+
+    // Moves the subscribers and registries of `other` over to this handle's.
+    pub(crate) fn adopt(&self, other: &Events<M>) {
+        if Arc::ptr_eq(&self.subscribers, &other.subscribers) {
+            return
+        }
+        let moved: Vec<_> = other.subscribers.lock().unwrap().drain(..).collect();
+        self.subscribers.lock().unwrap().extend(moved);
+        let moved: Vec<_> = other.registries.lock().unwrap().drain(..).collect();
+        self.registries.lock().unwrap().extend(moved);
+    }
+}
+
+impl<M> Events<M> where M: Clone + fmt::Debug {
+    // Sends the event to every subscriber, dropping the ones that went away. With the
+    // `log` or `tracing` feature enabled, the event is also recorded under the "marid"
+    // target, tagged with the runner's name.
+    pub(crate) fn emit(&self, kind: EventKind<M>) {
+        logging::event(&label(self), &kind);
+        let event = Event {
+            index: self.index,
+            name: self.name.clone(),
+            parents: self.parents.clone(),
+            kind: kind,
+            time: SystemTime::now(),
+        };
+        for registry in self.registries.lock().unwrap().iter() {
+            registry.record(&event);
+        }
+        self.subscribers.lock().unwrap().retain(|s| s.send(event.clone()).is_ok());
+    }
+}
+
+impl<M> Clone for Events<M> {
+    fn clone(&self) -> Events<M> {
+        Events {
+            subscribers: self.subscribers.clone(),
+            registries: self.registries.clone(),
+            index: self.index,
+            name: self.name.clone(),
+            parents: self.parents.clone(),
+        }
+    }
+}
+
+// Names the runner whose events are emitted by the handle, for log records.
+pub fn label<'a, M>(events: &'a Events<M>) -> Label<'a> {
+    Label {
+        index: events.index,
+        name: events.name.as_ref().map(|n| &n[..]),
+    }
+}
+
+// Names the runner the event is about by its path from the root: the labels of the
+// members enclosing it and its own, joined by '/', as in "outer/inner/member 0".
+pub fn path<M>(event: &Event<M>) -> String {
+    let label = Label { index: event.index, name: event.name.as_ref().map(|n| &n[..]) };
+    let mut path = String::new();
+    for parent in &event.parents {
+        path.push_str(parent);
+        path.push('/');
+    }
+    let _ = write!(path, "{}", label);
+    path
+}
+
+// Describes the outcome of setup() or run(), unless the runner panicked.
+pub fn outcome_event<M, F>(res: &Result<(), MaridError>, event: F) -> EventKind<M>
+    where F: FnOnce(Result<(), String>) -> EventKind<M> {
+    match *res {
+        Ok(()) => event(Ok(())),
+        Err(ref e) => match e.downcast_ref::<PanicError>() {
+            Some(panic) => EventKind::Panicked(panic.message.clone()),
+            None => event(Err(e.to_string())),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Events, EventKind};
+    use Signal;
+
+    #[test]
+    fn test_events_member() {
+        let events = Events::new();
+        let rc = events.subscribe();
+        events.member(2, Some("web")).emit(EventKind::SignalDelivered(Signal::HUP));
+        let event = rc.recv().unwrap();
+        assert_eq!(event.index, Some(2));
+        assert_eq!(event.name, Some("web".to_string()));
+        assert_eq!(event.kind, EventKind::SignalDelivered(Signal::HUP));
+        assert!(event.parents.is_empty());
+    }
+
+    #[test]
+    fn test_events_nested_member() {
+        let events = Events::<Signal>::new();
+        let rc = events.subscribe();
+        events.member(0, Some("outer")).member(1, None).member(2, None).emit(EventKind::Running);
+        let event = rc.recv().unwrap();
+        assert_eq!(event.parents, vec!("outer".to_string(), "member 1".to_string()));
+        assert_eq!(event.index, Some(2));
+    }
+
+    #[test]
+    fn test_events_adopt() {
+        let parent = Events::<Signal>::new();
+        let child = Events::new();
+        let rc = child.subscribe();
+        drop(parent.subscribe());
+        parent.adopt(&child);
+        parent.emit(EventKind::Running);
+        assert_eq!(rc.recv().unwrap().kind, EventKind::Running);
+        // The dropped subscriber has been removed.
+        assert_eq!(parent.subscribers.lock().unwrap().len(), 1);
+    }
+}
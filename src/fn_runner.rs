@@ -3,7 +3,7 @@ use traits::{Receiver};
 use {MaridError, Runner, Signal};
 
 /// A Runner type that is constructed with a FnOnce closure.
-pub type FnRunner = Thunk<'static, Receiver<Signal>, Result<(), MaridError>>;
+pub type FnRunner<M = Signal> = Thunk<'static, Receiver<M>, Result<(), MaridError>>;
 
 impl FnRunner {
     /// Create a new FnRunner
@@ -13,8 +13,16 @@ impl FnRunner {
         }
 }
 
-impl Runner for FnRunner {
-    fn run(self: Box<Self>, signals: Receiver<Signal>) -> Result<(), MaridError> {
+impl<M> FnRunner<M> {
+    /// Create a new FnRunner receiving messages of any type.
+    pub fn receiving<F>(func: F) -> FnRunner<M>
+        where F: FnOnce(Receiver<M>) -> Result<(), MaridError>, F: Send + 'static {
+            Thunk::with_arg(func)
+        }
+}
+
+impl<M> Runner<M> for FnRunner<M> {
+    fn run(self: Box<Self>, signals: Receiver<M>) -> Result<(), MaridError> {
         (*self).invoke(signals)
     }
 
@@ -0,0 +1,301 @@
+use traits::{Runner, Signal, Receiver};
+use ready::Ready;
+use events::{Event, Events};
+use process::MaridProcess;
+use tree::{SharedTree, Watched};
+use {MaridError};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+// How long a client may take to send its request, or to read the response.
+const IO_TIMEOUT_MS: u64 = 5000;
+
+/// A Runner serving the health of a process tree over HTTP.
+///
+/// The server answers on a local TCP port:
+///
+/// * `GET /healthz` returns 200 for as long as the server runs.
+/// * `GET /readyz` returns 200 once every runner of the tree, and every watched
+///   process, has reported that it is ready or has exited successfully, and 503
+///   otherwise.
+/// * `GET /status` returns a JSON dump of the status of each of them. Runners are named
+///   by their path from the root, such as `inner/member 0` for the first member of a
+///   Composer named `inner`.
+///
+/// As a member of a Composer, the server follows the lifecycle events of the whole tree
+/// started by the MaridProcess. Processes outside of the tree are followed with
+/// `watch()`. Each connection is served on its own thread.
+///
+/// The server exits once it receives its shutdown signal, ignoring any other signal.
+pub struct HealthServer<M = Signal> {
+    listener: TcpListener,
+    shutdown_signal: M,
+    events: Option<mpsc::Receiver<Event<M>>>,
+    watched: Vec<Watched>,
+}
+
+impl<M> HealthServer<M> where M: Clone + PartialEq + fmt::Debug + Send + 'static {
+    /// Creates a server listening on the given address, which exits once it receives the
+    /// shutdown_signal.
+    pub fn bind<A: ToSocketAddrs>(addr: A, shutdown_signal: M) -> io::Result<HealthServer<M>> {
+        let listener = try!(TcpListener::bind(addr));
+        Ok(HealthServer {
+            listener: listener,
+            shutdown_signal: shutdown_signal,
+            events: None,
+            watched: vec!(),
+        })
+    }
+
+    /// Returns the address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Reports the status of the process under the given name as well.
+    pub fn watch<N, P>(mut self, name: N, process: MaridProcess<P>) -> HealthServer<M>
+        where N: Into<String>, P: Clone + fmt::Debug + Send + 'static {
+        self.watched.push((name.into(), Box::new(move || process.status())));
+        self
+    }
+}
+
+impl<M> Runner<M> for HealthServer<M> where M: Clone + PartialEq + fmt::Debug + Send + 'static {
+    fn observe(&mut self, events: Events<M>) {
+        self.events = Some(events.subscribe());
+    }
+
+    fn setup(&mut self) -> Result<(), MaridError> {
+        Ok(())
+    }
+
+    fn run(self: Box<Self>, signals: Receiver<M>) -> Result<(), MaridError> {
+        self.run_ready(signals, Ready::unobserved())
+    }
+
+    fn run_ready(self: Box<Self>, signals: Receiver<M>, ready: Ready) -> Result<(), MaridError> {
+        let server = *self;
+        let addr = try!(server.listener.local_addr());
+        let tree = Arc::new(SharedTree::new(server.watched));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let follower = server.events.map(|events| {
+            let tree = tree.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || tree.follow(events, &stopped))
+        });
+
+        let listener = server.listener;
+        let accept_stopped = stopped.clone();
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_stopped.load(Ordering::SeqCst) {
+                    break
+                }
+                if let Ok(stream) = stream {
+                    let tree = tree.clone();
+                    thread::spawn(move || serve(stream, &tree));
+                }
+            }
+        });
+        ready.close();
+
+        while let Some(sig) = signals.recv() {
+            if sig == server.shutdown_signal {
+                break
+            }
+        }
+        stopped.store(true, Ordering::SeqCst);
+        // Wakes up the accepting thread, which finds the server stopped.
+        let _ = TcpStream::connect(addr);
+        let _ = handle.join();
+        if let Some(follower) = follower {
+            let _ = follower.join();
+        }
+        Ok(())
+    }
+}
+
+fn serve(stream: TcpStream, tree: &SharedTree) -> io::Result<()> {
+    try!(stream.set_read_timeout(Some(Duration::from_millis(IO_TIMEOUT_MS))));
+    try!(stream.set_write_timeout(Some(Duration::from_millis(IO_TIMEOUT_MS))));
+    let mut reader = BufReader::new(try!(stream.try_clone()));
+    let mut request = String::new();
+    try!(reader.read_line(&mut request));
+    // The headers are read and ignored.
+    loop {
+        let mut line = String::new();
+        if try!(reader.read_line(&mut line)) == 0 || line.trim().is_empty() {
+            break
+        }
+    }
+
+    let mut parts = request.split_whitespace();
+    let (method, path) = (parts.next(), parts.next());
+    let (status, content_type, body) = match (method, path) {
+        (Some("GET"), Some("/healthz")) => ("200 OK", "text/plain", "ok\n".to_string()),
+        (Some("GET"), Some("/readyz")) => {
+            if tree.lock().ready() {
+                ("200 OK", "text/plain", "ready\n".to_string())
+            } else {
+                ("503 Service Unavailable", "text/plain", "not ready\n".to_string())
+            }
+        },
+        (Some("GET"), Some("/status")) => ("200 OK", "application/json", tree.lock().json()),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "method not allowed\n".to_string()),
+    };
+
+    let mut stream = stream;
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           status, content_type, body.len(), body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HealthServer;
+    use traits::{Runner, Process, Signal};
+    use composer::Composer;
+    use process::MaridProcess;
+    use test_helpers::TestRunner;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use chan;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    // Waits for the server to follow the events that made the tree ready.
+    fn wait_ready(addr: SocketAddr) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !get(addr, "/readyz").starts_with("HTTP/1.1 200 OK\r\n") {
+            assert!(Instant::now() < deadline, "Server did not become ready");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_health_server() {
+        let server = HealthServer::bind("127.0.0.1:0", Signal::INT).unwrap();
+        let addr = server.local_addr().unwrap();
+        let (sn, _rc) = chan::sync(1);
+        let runner = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+        let composer = Composer::new(vec!(), Signal::INT)
+            .member("web", runner)
+            .member("health", Box::new(server));
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(Box::new(composer), signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+        wait_ready(addr);
+
+        // A client that never sends its request does not hold back the others.
+        let _idle = TcpStream::connect(addr).unwrap();
+        assert!(get(addr, "/healthz").starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(get(addr, "/readyz").ends_with("\r\n\r\nready\n"));
+        let status = get(addr, "/status");
+        assert!(status.contains("{\"path\":\"web\",\"index\":0,\"name\":\"web\",\"status\":\"ready\"}"));
+        assert!(status.contains("{\"path\":\"process\",\"index\":null,\"name\":null,\"status\":\"ready\"}"));
+        assert!(get(addr, "/nope").starts_with("HTTP/1.1 404"));
+
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+        assert!(TcpStream::connect(addr).is_err());
+    }
+
+    #[test]
+    fn test_health_server_nested() {
+        let server = HealthServer::bind("127.0.0.1:0", Signal::INT).unwrap();
+        let addr = server.local_addr().unwrap();
+        let (sn, _rc) = chan::sync(2);
+        let inner = Composer::new(vec!(Box::new(TestRunner::new(0, sn.clone())) as Box<Runner + Send>),
+                                  Signal::INT);
+        let outer = Composer::new(vec!(Box::new(TestRunner::new(1, sn)) as Box<Runner + Send>), Signal::INT)
+            .member("inner", Box::new(inner))
+            .member("health", Box::new(server));
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(Box::new(outer), signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+        wait_ready(addr);
+
+        // The first members of both Composers are kept apart.
+        let status = get(addr, "/status");
+        assert!(status.contains("{\"path\":\"member 0\",\"index\":0,\"name\":null,\"status\":\"ready\"}"));
+        assert!(status.contains("{\"path\":\"inner/member 0\",\"index\":0,\"name\":null,\"status\":\"ready\"}"));
+        assert!(status.contains("{\"path\":\"inner\",\"index\":1,\"name\":\"inner\",\"status\":\"ready\"}"));
+
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+    }
+
+    #[test]
+    fn test_health_server_removed() {
+        let server = HealthServer::bind("127.0.0.1:0", Signal::INT).unwrap();
+        let addr = server.local_addr().unwrap();
+        let (sn, rc) = chan::sync(2);
+        let mut composer = Composer::new(vec!(), Signal::INT)
+            .member("web", Box::new(TestRunner::new(0, sn.clone())) as Box<Runner + Send>)
+            .member("worker", Box::new(TestRunner::new(1, sn)))
+            .member("health", Box::new(server));
+        let handle = composer.handle();
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(Box::new(composer), signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+        wait_ready(addr);
+
+        // The member fails on TERM, which does not hold back the tree once it is removed.
+        assert!(handle.remove("web", Signal::TERM).is_ok());
+        assert!(!rc.recv().unwrap());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while get(addr, "/status").contains("\"path\":\"web\"") {
+            assert!(Instant::now() < deadline, "Member was not removed");
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(get(addr, "/readyz").starts_with("HTTP/1.1 200 OK\r\n"));
+
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+    }
+
+    #[test]
+    fn test_health_server_watch() {
+        let (sn, _rc) = chan::sync(1);
+        let runner = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let watched = MaridProcess::start(runner, signal_sn, signal_rc);
+        assert!(watched.ready().is_ok());
+
+        let server = HealthServer::bind("127.0.0.1:0", Signal::INT).unwrap().watch("worker", watched.clone());
+        let addr = server.local_addr().unwrap();
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(Box::new(server), signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+        wait_ready(addr);
+
+        // Only the shutdown signal stops the server.
+        process.signal(Signal::HUP);
+        assert!(get(addr, "/healthz").starts_with("HTTP/1.1 200 OK\r\n"));
+
+        watched.signal(Signal::HUP);
+        assert!(watched.wait().is_err());
+        assert!(get(addr, "/readyz").starts_with("HTTP/1.1 503"));
+        assert!(get(addr, "/status").contains("\"processes\":[{\"name\":\"worker\",\"status\":\"failed\"}]"));
+
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+    }
+}
@@ -33,7 +33,7 @@
 //! // Start all Runners in separate threads.
 //! let process = launch(composer, signals);
 //!
-//! // Wait until all Runners have been setup.
+//! // Wait until all Runners have been setup and are ready.
 //! assert!(process.ready().is_ok());
 //!
 //! // Send a shutdown signal to all Runners.
@@ -42,43 +42,152 @@
 //! // Wait until all Runners have finished.
 //! assert!(process.wait().is_ok());
 //! ```
+//!
+//! # Logging
+//!
+//! With the `log` or `tracing` feature enabled, the lifecycle events of every runner, and
+//! the teardown of a Composer's members, are recorded under the "marid" target, tagged
+//! with the name of the runner.
 #[macro_use]
 extern crate chan;
 extern crate chan_signal;
-extern crate crossbeam;
+extern crate libc;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
 mod traits;
 pub use traits::{Signal, Sender, Receiver, Process, Runner};
 
+mod ready;
+pub use ready::Ready;
+
+mod events;
+pub use events::{Events, Event, EventKind};
+
+mod logging;
+
+mod metrics;
+pub use metrics::{Metrics, RunnerMetrics, Exits};
+
 mod thunk;
 mod fn_runner;
 pub use fn_runner::FnRunner;
 
 mod composer;
-pub use composer::Composer;
+pub use composer::{Composer, StartupMode, ShutdownPolicy, Route, MemberError, GroupError, GroupHandle, MembershipError};
 
 mod process;
-pub use process::{MaridProcess, ProcessError};
+pub use process::{MaridProcess, ProcessError, SharedError, Outcome, Status, DropPolicy};
+
+mod panic;
+pub use panic::PanicError;
+
+mod shutdown;
+pub use shutdown::{Escalation, StillRunning};
+
+mod restart;
+pub use restart::{Restart, Backoff};
+
+mod supervisor;
+pub use supervisor::{Supervisor, SupervisorHandle, ChildSpec, RestartType, Strategy, RestartLimitError};
+
+mod signals;
+pub use signals::{SignalSource, OsSignals, ManualSignals, MergedSignals};
+
+mod tree;
+mod health;
+pub use health::HealthServer;
+
+mod control;
+pub use control::ControlServer;
+
+mod command;
+pub use command::{CommandRunner, CommandError};
 
 use std::error::Error;
+use std::thread;
+use std::fmt;
 /// Error type for Marid Runners.
-pub type MaridError = Box<Error + Send>;
+///
+/// Errors are Sync so that a single result can be shared by every caller waiting on a
+/// MaridProcess. Releases before 0.2 only required errors to be Send, see the README for
+/// how to upgrade runners returning errors that are not Sync.
+pub type MaridError = Box<Error + Send + Sync>;
 
-/// Launch the specified runner as well as listen on the specified signals.
+/// Launch the specified runner, delivering it the signals of the source.
 ///
-/// # Safety
+/// The source can be any SignalSource, such as a list of OS signals, or a
+/// `ManualSignals` in tests. Only `OsSignals` depend on being started before any
+/// threads are spawned in the process.
 ///
-/// This must be called before any threads are spawned in the process to
-/// ensure appropriate signal handling behavior.
-pub fn launch<R>(runner: R, signals: Vec<Signal>) -> MaridProcess
-where R: Runner + Send + 'static {
+/// The runner may receive any message type that can be built from a `Signal`, such
+/// as an application enum wrapping OS signals alongside its own commands. Such
+/// commands are sent through the returned MaridProcess.
+///
+/// Signals of the source are forwarded until the runner has exited.
+pub fn launch<R, M, S>(runner: R, source: S) -> MaridProcess<M>
+where R: Runner<M> + Send + 'static,
+      M: From<Signal> + Clone + fmt::Debug + Send + 'static,
+      S: SignalSource + 'static {
+    let (source_send, source_recv) = chan::sync(1024);
+    Box::new(source).start(source_send);
+
     let (signal_send, signal_recv) = chan::sync(1024);
-    for sig in signals {
-        chan_signal::notify_on(&signal_send, sig);
-    }
+    let (running_send, running_recv) = chan::sync::<()>(0);
+    let forward = signal_send.clone();
+    thread::spawn(move || {
+        loop {
+            let sig;
+            chan_select! {
+                source_recv.recv() -> s => sig = s,
+                running_recv.recv() => return,
+            }
+            let sig = match sig {
+                Some(sig) => M::from(sig),
+                None => return,
+            };
+            chan_select! {
+                forward.send(sig) => {},
+                running_recv.recv() => return,
+            }
+        }
+    });
 
+    let runner = Launched {
+        runner: runner,
+        _running: running_send,
+    };
     MaridProcess::start(Box::new(runner), signal_send, signal_recv)
 }
 
+// A runner started by launch(). Dropping it once the runner has exited, or failed its
+// setup, closes the channel on which the forwarding thread waits.
+struct Launched<R> {
+    runner: R,
+    _running: Sender<()>,
+}
+
+impl<R, M> Runner<M> for Launched<R> where R: Runner<M> {
+    fn run(self: Box<Self>, signals: Receiver<M>) -> Result<(), MaridError> {
+        let launched = *self;
+        Box::new(launched.runner).run(signals)
+    }
+
+    fn setup(&mut self) -> Result<(), MaridError> {
+        self.runner.setup()
+    }
+
+    fn run_ready(self: Box<Self>, signals: Receiver<M>, ready: Ready) -> Result<(), MaridError> {
+        let launched = *self;
+        Box::new(launched.runner).run_ready(signals, ready)
+    }
+
+    fn observe(&mut self, events: Events<M>) {
+        self.runner.observe(events)
+    }
+}
+
 // TODO: Make this module more useable and document behavior.
 pub mod test_helpers;
@@ -0,0 +1,105 @@
+use events::EventKind;
+use std::fmt;
+use std::time::Duration;
+
+/// Names the runner a record is about, as the `runner` field of every record.
+pub struct Label<'a> {
+    pub index: Option<usize>,
+    pub name: Option<&'a str>,
+}
+
+impl<'a> fmt::Display for Label<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.name, self.index) {
+            (Some(name), _) => write!(f, "{}", name),
+            (None, Some(idx)) => write!(f, "member {}", idx),
+            (None, None) => write!(f, "process"),
+        }
+    }
+}
+
+// Emits a record through each enabled backend, at the given `log` and `tracing` level,
+// under the "marid" target.
+#[cfg(any(feature = "log", feature = "tracing"))]
+macro_rules! record {
+    ($log:ident, $tracing:ident, $runner:expr, $($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::$log!(target: "marid", "[{}] {}", $runner, format_args!($($arg)+));
+        #[cfg(feature = "tracing")]
+        ::tracing::$tracing!(target: "marid", runner = %$runner, "{}", format_args!($($arg)+));
+    }}
+}
+
+// Without a backend the arguments are only borrowed, so that nothing is formatted.
+#[cfg(not(any(feature = "log", feature = "tracing")))]
+macro_rules! record {
+    ($log:ident, $tracing:ident, $runner:expr, $($arg:tt)+) => {{
+        let _ = &$runner;
+        let _ = format_args!($($arg)+);
+    }}
+}
+
+pub fn event<M: fmt::Debug>(runner: &Label, kind: &EventKind<M>) {
+    match *kind {
+        EventKind::SetupStarted => record!(debug, debug, runner, "setup started"),
+        EventKind::SetupFinished(Ok(())) => record!(info, info, runner, "setup finished"),
+        EventKind::SetupFinished(Err(ref e)) => record!(error, error, runner, "setup failed: {}", e),
+        EventKind::Running => record!(info, info, runner, "running"),
+        EventKind::Ready => record!(info, info, runner, "ready"),
+        EventKind::SignalDelivered(ref sig) => record!(debug, debug, runner, "delivered {:?}", sig),
+        EventKind::Exited(Ok(())) => record!(info, info, runner, "exited"),
+        EventKind::Exited(Err(ref e)) => record!(error, error, runner, "exited with error: {}", e),
+        EventKind::Restarted => record!(warn, warn, runner, "restarted"),
+        EventKind::Panicked(ref msg) => record!(error, error, runner, "panicked: {}", msg),
+        EventKind::Removed => record!(debug, debug, runner, "removed"),
+    }
+}
+
+pub fn teardown<M: fmt::Debug>(runner: &Label, signal: &M) {
+    record!(info, info, runner, "tearing down members with {:?}", signal)
+}
+
+pub fn escalate<M: fmt::Debug>(runner: &Label, signal: &M, grace: Duration) {
+    record!(warn, warn, runner, "members still running after {:?}, sending {:?}", grace, signal)
+}
+
+pub fn abandon(runner: &Label) {
+    record!(error, error, runner, "abandoned while still running")
+}
+
+#[cfg(all(test, feature = "log"))]
+mod tests {
+    use super::{Label, event};
+    use events::EventKind;
+    use log::{self, Log, Metadata, Record};
+    use std::sync::Mutex;
+    use Signal;
+
+    struct Capture(Mutex<Vec<String>>);
+
+    impl Log for Capture {
+        fn enabled(&self, _metadata: &Metadata) -> bool { true }
+
+        fn log(&self, record: &Record) {
+            let line = format!("{} {} {}", record.level(), record.target(), record.args());
+            self.0.lock().unwrap().push(line);
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_log_records() {
+        let capture: &'static Capture = Box::leak(Box::new(Capture(Mutex::new(vec!()))));
+        log::set_logger(capture).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let web = Label { index: Some(1), name: Some("web") };
+        event(&web, &EventKind::SignalDelivered(Signal::TERM));
+        event(&Label { index: Some(2), name: None }, &EventKind::<Signal>::Exited(Err("boom".to_string())));
+
+        let lines = capture.0.lock().unwrap();
+        assert!(lines.contains(&"DEBUG marid [web] delivered TERM".to_string()));
+        assert!(lines.contains(&"ERROR marid [member 2] exited with error: boom".to_string()));
+    }
+}
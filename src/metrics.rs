@@ -0,0 +1,262 @@
+use events::{self, Event, EventKind};
+use std::fmt;
+use std::fmt::Write;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// How many times a runner exited, by outcome.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub struct Exits {
+    /// Exits without an error.
+    pub ok: u64,
+    /// Exits with an error, including failed setups.
+    pub failed: u64,
+    /// Panics during setup or while running.
+    pub panicked: u64,
+}
+
+/// A snapshot of the metrics of a single runner.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct RunnerMetrics {
+    /// Index of the member, or None for the runner started by a MaridProcess.
+    pub index: Option<usize>,
+    /// Name of the member, if it was given one.
+    pub name: Option<String>,
+    /// Labels of the enclosing members and of the runner itself, joined by '/', as in
+    /// "outer/inner/member 0". The runner started by a MaridProcess is "process".
+    pub path: String,
+    /// How long the last setup() took, once it returned.
+    pub setup_duration: Option<Duration>,
+    /// Time from the start of setup() until the runner reported that it is ready.
+    pub time_to_ready: Option<Duration>,
+    /// How long the runner has been running, or ran until it last exited.
+    pub uptime: Option<Duration>,
+    /// How many times the runner exited, by outcome.
+    pub exits: Exits,
+    /// How many times the runner was replaced after exiting.
+    pub restarts: u64,
+    /// How many signals were delivered to the runner, by their Debug representation.
+    pub signals: BTreeMap<String, u64>,
+}
+
+// The name, help text and value of a gauge, in seconds, of each runner.
+type Gauge = (&'static str, &'static str, fn(&RunnerMetrics) -> Option<Duration>);
+
+struct Entry {
+    metrics: RunnerMetrics,
+    setup_started: Option<SystemTime>,
+    running_since: Option<SystemTime>,
+    stopped: Option<SystemTime>,
+}
+
+/// A registry of the metrics of runners, built from their lifecycle events.
+///
+/// Every MaridProcess records the events of its runner and of the runner's members into
+/// a registry, returned by `MaridProcess::metrics()`. Runners are told apart by their
+/// path from the root, so that the members of nested Composers are kept apart. Clones
+/// share the same metrics.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+impl Metrics {
+    /// Creates an empty registry.
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    // Updates the metrics of the runner the event is about.
+    pub(crate) fn record<M: fmt::Debug>(&self, event: &Event<M>) {
+        let mut entries = self.entries.lock().unwrap();
+        let path = events::path(event);
+        let pos = entries.iter().position(|e| e.metrics.path == path);
+        let idx = match pos {
+            Some(idx) => idx,
+            None => {
+                entries.push(Entry {
+                    metrics: RunnerMetrics {
+                        index: event.index,
+                        name: event.name.clone(),
+                        path: path,
+                        ..RunnerMetrics::default()
+                    },
+                    setup_started: None,
+                    running_since: None,
+                    stopped: None,
+                });
+                entries.len() - 1
+            },
+        };
+
+        let entry = &mut entries[idx];
+        match event.kind {
+            EventKind::SetupStarted => entry.setup_started = Some(event.time),
+            EventKind::SetupFinished(ref res) => {
+                entry.metrics.setup_duration = entry.setup_started.map(|t| since(t, event.time));
+                if res.is_err() {
+                    entry.metrics.exits.failed += 1;
+                }
+            },
+            EventKind::Running => {
+                entry.running_since = Some(event.time);
+                entry.stopped = None;
+            },
+            EventKind::Ready => {
+                let start = entry.setup_started.or(entry.running_since);
+                entry.metrics.time_to_ready = start.map(|t| since(t, event.time));
+            },
+            EventKind::SignalDelivered(ref sig) => {
+                *entry.metrics.signals.entry(format!("{:?}", sig)).or_insert(0) += 1;
+            },
+            EventKind::Exited(ref res) => {
+                entry.stopped = Some(event.time);
+                match *res {
+                    Ok(()) => entry.metrics.exits.ok += 1,
+                    Err(_) => entry.metrics.exits.failed += 1,
+                }
+            },
+            EventKind::Restarted => entry.metrics.restarts += 1,
+            // The metrics of a removed member are kept.
+            EventKind::Removed => {},
+            EventKind::Panicked(_) => {
+                entry.stopped = Some(event.time);
+                entry.metrics.exits.panicked += 1;
+            },
+        }
+    }
+
+    /// Returns the metrics of every runner, in the order they were first seen.
+    pub fn snapshot(&self) -> Vec<RunnerMetrics> {
+        let now = SystemTime::now();
+        self.entries.lock().unwrap().iter().map(|e| {
+            let mut metrics = e.metrics.clone();
+            metrics.uptime = e.running_since.map(|t| since(t, e.stopped.unwrap_or(now)));
+            metrics
+        }).collect()
+    }
+
+    /// Renders a snapshot in the Prometheus text exposition format.
+    ///
+    /// Every sample is labelled with the runner's path.
+    pub fn prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+
+        let gauges: [Gauge; 3] = [
+            ("marid_setup_duration_seconds", "Duration of the last setup of the runner.",
+             |m| m.setup_duration),
+            ("marid_time_to_ready_seconds", "Time until the runner reported that it is ready.",
+             |m| m.time_to_ready),
+            ("marid_uptime_seconds", "Time the runner has been running.",
+             |m| m.uptime),
+        ];
+        for &(name, help, value) in gauges.iter() {
+            header(&mut out, name, help, "gauge");
+            for m in &snapshot {
+                if let Some(d) = value(m) {
+                    let _ = writeln!(out, "{}{{runner=\"{}\"}} {}", name, runner(m), seconds(d));
+                }
+            }
+        }
+
+        header(&mut out, "marid_exits_total", "Exits of the runner, by outcome.", "counter");
+        for m in &snapshot {
+            let outcomes = [("ok", m.exits.ok), ("failed", m.exits.failed), ("panicked", m.exits.panicked)];
+            for &(outcome, count) in outcomes.iter() {
+                let _ = writeln!(out, "marid_exits_total{{runner=\"{}\",outcome=\"{}\"}} {}",
+                                 runner(m), outcome, count);
+            }
+        }
+
+        header(&mut out, "marid_restarts_total", "Restarts of the runner.", "counter");
+        for m in &snapshot {
+            let _ = writeln!(out, "marid_restarts_total{{runner=\"{}\"}} {}", runner(m), m.restarts);
+        }
+
+        header(&mut out, "marid_signals_total", "Signals delivered to the runner, by kind.", "counter");
+        for m in &snapshot {
+            for (sig, count) in &m.signals {
+                let _ = writeln!(out, "marid_signals_total{{runner=\"{}\",signal=\"{}\"}} {}",
+                                 runner(m), escape(sig), count);
+            }
+        }
+        out
+    }
+}
+
+fn since(earlier: SystemTime, later: SystemTime) -> Duration {
+    later.duration_since(earlier).unwrap_or(Duration::from_secs(0))
+}
+
+fn seconds(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn runner(m: &RunnerMetrics) -> String {
+    escape(&m.path)
+}
+
+// Escapes a label value of the text exposition format.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Metrics, Exits};
+    use events::{Event, EventKind};
+    use std::time::{Duration, SystemTime};
+    use Signal;
+
+    fn event(name: &str, kind: EventKind, time: SystemTime) -> Event {
+        Event { index: Some(0), name: Some(name.to_string()), parents: vec!(), kind: kind, time: time }
+    }
+
+    #[test]
+    fn test_metrics_record() {
+        let metrics = Metrics::new();
+        let start = SystemTime::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        metrics.record(&event("web", EventKind::SetupStarted, at(0)));
+        metrics.record(&event("web", EventKind::SetupFinished(Ok(())), at(20)));
+        metrics.record(&event("web", EventKind::Running, at(20)));
+        metrics.record(&event("web", EventKind::Ready, at(50)));
+        metrics.record(&event("web", EventKind::SignalDelivered(Signal::HUP), at(60)));
+        metrics.record(&event("web", EventKind::SignalDelivered(Signal::HUP), at(70)));
+        metrics.record(&event("web", EventKind::Panicked("boom".to_string()), at(80)));
+        metrics.record(&event("web", EventKind::Restarted, at(80)));
+        metrics.record(&event("web", EventKind::Running, at(90)));
+        metrics.record(&event("web", EventKind::Exited(Ok(())), at(100)));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        let web = &snapshot[0];
+        assert_eq!(web.setup_duration, Some(Duration::from_millis(20)));
+        assert_eq!(web.time_to_ready, Some(Duration::from_millis(50)));
+        assert_eq!(web.uptime, Some(Duration::from_millis(10)));
+        assert_eq!(web.exits, Exits { ok: 1, failed: 0, panicked: 1 });
+        assert_eq!(web.restarts, 1);
+        assert_eq!(web.signals.get("HUP"), Some(&2));
+    }
+
+    #[test]
+    fn test_metrics_prometheus() {
+        let metrics = Metrics::new();
+        let now = SystemTime::now();
+        metrics.record(&event("web \"1\"", EventKind::SignalDelivered(Signal::TERM), now));
+        metrics.record(&event("web \"1\"", EventKind::Exited(Err("boom".to_string())), now));
+
+        let text = metrics.prometheus();
+        assert!(text.contains("# TYPE marid_exits_total counter\n"));
+        assert!(text.contains("marid_exits_total{runner=\"web \\\"1\\\"\",outcome=\"failed\"} 1\n"));
+        assert!(text.contains("marid_signals_total{runner=\"web \\\"1\\\"\",signal=\"TERM\"} 1\n"));
+        assert!(!text.contains("marid_uptime_seconds{"));
+    }
+}
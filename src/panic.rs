@@ -0,0 +1,48 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use {MaridError};
+
+/// Error used in place of the result of a runner that panicked.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PanicError {
+    /// The message the runner panicked with.
+    pub message: String,
+}
+
+impl fmt::Display for PanicError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Runner panicked: {}", self.message)
+    }
+}
+
+impl Error for PanicError {
+    fn description(&self) -> &str {
+        "Runner panicked"
+    }
+}
+
+/// Calls the function, returning the panic message if it panics.
+pub fn catch<F, T>(func: F) -> Result<T, String> where F: FnOnce() -> T {
+    panic::catch_unwind(AssertUnwindSafe(func)).map_err(message)
+}
+
+/// Calls a Runner function, turning a panic into a PanicError.
+pub fn catch_runner<F>(func: F) -> Result<(), MaridError>
+    where F: FnOnce() -> Result<(), MaridError> {
+    match catch(func) {
+        Ok(res) => res,
+        Err(message) => Err(Box::new(PanicError { message: message })),
+    }
+}
+
+fn message(payload: Box<Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(s) => *s,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(s) => s.to_string(),
+            Err(_) => "unknown panic payload".to_string(),
+        },
+    }
+}
@@ -1,44 +1,64 @@
 use std::thread;
 use std::fmt;
+use std::mem;
 use std::error::Error;
-use std::sync::mpsc;
-use std::cell::Cell;
+use std::sync::{Arc, Mutex, Condvar, mpsc};
+use std::time::{Duration, Instant};
 use traits::{Runner, Process, Sender, Receiver, Signal};
+use ready::Ready;
+use events::{Events, Event, EventKind, outcome_event};
+use metrics::Metrics;
+use panic::{catch_runner, PanicError};
+use shutdown::Escalation;
 use {MaridError};
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
-enum ProcState {
-    Init,
-    SetupDone,
-    Finished,
+#[derive(Debug, Eq, PartialEq, Clone)]
+/// The current state of the runner started by a MaridProcess.
+pub enum Status {
+    /// The runner is being setup.
+    Initializing,
+    /// The runner is running, and has reported that it is ready.
+    Ready,
+    /// The runner has finished its setup and is running, but has not yet reported that
+    /// it is ready.
+    Running,
+    /// The runner has exited. A failure is described by the error's message.
+    Exited(Result<(), String>),
+    /// The runner panicked during setup or while running.
+    ///
+    /// The panic message is returned by `ready()` or `wait()` as a
+    /// `ProcessError::RunnerPanicked`.
+    Panicked,
 }
 
-
 #[derive(Debug, Eq, PartialEq, Clone)]
 /// Error type for a running Process.
 pub enum ProcessError<E> {
     /// The associated runner returned an error which can be found as the enclosed argument.
     RunnerError(E),
-    /// The associated result has already been received by a caller.
-    ///
-    /// This occurs when calling wait or ready more than once.
-    ResultAlreadyGiven,
-    /// The process was not able to recieve a result from the runner. Something has gone wrong
-    /// on the runner's thread.
-    CouldNotRecvResult,
+    /// The result was not available before the timeout elapsed. The call can be made again.
+    Timeout,
+    /// The associated runner panicked, with the enclosed message.
+    RunnerPanicked(String),
+    /// The associated runner did not exit before the deadline of an Escalation, and has
+    /// been left running.
+    StillRunning,
 }
 
-impl<E: Error> fmt::Display for ProcessError<E> {
+impl<E: fmt::Display> fmt::Display for ProcessError<E> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ProcessError::RunnerError(ref e) => {
                 write!(fmt, "{}", e)
             },
-            ProcessError::ResultAlreadyGiven => {
-                write!(fmt, "Already returned result to caller")
+            ProcessError::Timeout => {
+                write!(fmt, "Timed out waiting for result")
+            },
+            ProcessError::RunnerPanicked(ref msg) => {
+                write!(fmt, "Runner panicked: {}", msg)
             },
-            ProcessError::CouldNotRecvResult => {
-                write!(fmt, "Could not receive result from thread")
+            ProcessError::StillRunning => {
+                write!(fmt, "Runner did not exit before the shutdown deadline")
             }
         }
     }
@@ -50,11 +70,14 @@ impl<E: Error> Error for ProcessError<E> {
             ProcessError::RunnerError(ref e) => {
                 e.description()
             },
-            ProcessError::ResultAlreadyGiven => {
-                "Already returned result to caller"
+            ProcessError::Timeout => {
+                "Timed out waiting for result"
             },
-            ProcessError::CouldNotRecvResult => {
-                "Could not receive result from thread"
+            ProcessError::RunnerPanicked(_) => {
+                "Runner panicked"
+            },
+            ProcessError::StillRunning => {
+                "Runner did not exit before the shutdown deadline"
             }
         }
     }
@@ -66,104 +89,371 @@ impl<E: Error> From<E> for ProcessError<E> {
     }
 }
 
+/// Determines what happens to the runner's thread when the last handle to a
+/// MaridProcess is dropped.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum DropPolicy<M = Signal> {
+    /// Leave the runner's thread running. This is the default.
+    Detach,
+    /// Join the runner's thread, blocking until the runner exits.
+    Join,
+    /// Send the signal to the runner, then join its thread. If the runner has not exited
+    /// once the timeout has elapsed, its thread is left running.
+    SignalAndJoin(M, Duration),
+}
+
+/// Error shared between every caller waiting on a MaridProcess.
+pub type SharedError = Arc<MaridError>;
+
+/// The result of the runner started by a MaridProcess, as given to every caller waiting
+/// on it.
+pub type Outcome = Result<(), ProcessError<SharedError>>;
+
 /// Signifying the running state of a unit of work, a MaridProcess will spawn a
 /// new thread in order to not block the current thread.
 ///
-/// Upon dropping, an instance of a MaridProcess will join on the running thread,
-/// potentially blocking.
-pub struct MaridProcess {
-    setup_chan: mpsc::Receiver<Result<(), ProcessError<MaridError>>>,
-    run_chan: mpsc::Receiver<Result<(), ProcessError<MaridError>>>,
+/// A MaridProcess is a cloneable handle which can be shared between threads. Any number
+/// of callers may call `ready()` and `wait()`, each receiving the same result.
+///
+/// Upon dropping the last handle, a MaridProcess follows its DropPolicy. By default the
+/// running thread is detached; blocking on it must be asked for with `set_drop_policy()`.
+///
+/// A panic inside the runner is caught on the runner's thread and reported as a
+/// `ProcessError::RunnerPanicked`.
+///
+/// The runner is started with `Runner::run_ready()`, and `ready()` returns once it has
+/// closed its Ready handle. A runner that exits without doing so gives its result to
+/// `ready()` as well.
+#[derive(Clone)]
+pub struct MaridProcess<M = Signal> {
+    inner: Arc<Inner<M>>,
+}
 
-    signaler: Sender<Signal>,
+struct Inner<M> {
+    state: Arc<State>,
+    signaler: Sender<M>,
+    events: Events<M>,
     runner: Option<thread::JoinHandle<()>>,
-    state: Cell<ProcState>,
+    drop_policy: Mutex<DropPolicy<M>>,
 }
 
-// Be aware, ready/wait
-impl MaridProcess {
+// Written by the runner's thread, read by every handle.
+struct State {
+    shared: Mutex<Shared>,
+    changed: Condvar,
+}
+
+struct Shared {
+    status: Status,
+    ready: Option<Outcome>,
+    exit: Option<Outcome>,
+}
+
+impl<M> MaridProcess<M> where M: Clone + fmt::Debug + Send + 'static {
     /// Starts the specified runner with the given signal receiver.
-    pub fn start(runner: Box<Runner + Send>, signaler: Sender<Signal>, recv: Receiver<Signal>) -> MaridProcess {
-        let (setup_sn, setup_rc) = mpsc::channel();
-        let (run_sn, run_rc) = mpsc::channel();
+    pub fn start(runner: Box<Runner<M> + Send>, signaler: Sender<M>, recv: Receiver<M>) -> MaridProcess<M> {
+        let state = Arc::new(State {
+            shared: Mutex::new(Shared {
+                status: Status::Initializing,
+                ready: None,
+                exit: None,
+            }),
+            changed: Condvar::new(),
+        });
 
-        let handle = MaridProcess::spawn_run_thread(runner, recv, setup_sn, run_sn);
+        let events = Events::new();
+        // Created before the runner's registries are adopted, to be the process's own.
+        events.metrics();
+        let mut runner = runner;
+        runner.observe(events.clone());
+        let handle = MaridProcess::spawn_run_thread(runner, recv, state.clone(), events.clone());
 
         MaridProcess {
-            setup_chan: setup_rc,
-            run_chan: run_rc,
+            inner: Arc::new(Inner {
+                state: state,
+                signaler: signaler,
+                events: events,
+                runner: Some(handle),
+                drop_policy: Mutex::new(DropPolicy::Detach),
+            }),
+        }
+    }
+
+    /// Returns a Receiver of the lifecycle events of the runner, and of its members.
+    ///
+    /// Events emitted before subscribing are not received. Subscribe on a Composer
+    /// before starting it to receive every event.
+    pub fn subscribe(&self) -> mpsc::Receiver<Event<M>> {
+        self.inner.events.subscribe()
+    }
+
+    /// Returns the Metrics registry of the runner, and of its members.
+    pub fn metrics(&self) -> Metrics {
+        self.inner.events.metrics()
+    }
+
+    /// Sets the DropPolicy used once every handle to this MaridProcess has been dropped.
+    ///
+    /// The policy is shared by every clone of the handle, so the last policy set through
+    /// any of them, including by `detach()`, is the one followed.
+    pub fn set_drop_policy(&self, policy: DropPolicy<M>) {
+        *self.inner.drop_policy.lock().unwrap() = policy;
+    }
 
-            runner: Some(handle),
-            signaler: signaler,
-            state: Cell::new(ProcState::Init),
+    /// Drops this handle, leaving the runner's thread running once every other handle
+    /// has been dropped as well.
+    ///
+    /// This sets the DropPolicy of every clone of the handle to `DropPolicy::Detach`.
+    pub fn detach(self) {
+        self.set_drop_policy(DropPolicy::Detach);
+    }
+
+    /// Returns the current Status of the runner without blocking.
+    pub fn status(&self) -> Status {
+        self.inner.state.shared.lock().unwrap().status.clone()
+    }
+
+    /// Like `ready()`, but returns None instead of blocking if the runner has not yet
+    /// reported that it is ready.
+    pub fn try_ready(&self) -> Option<Outcome> {
+        self.inner.state.wait_for(|s| s.ready.as_ref(), Some(Duration::from_secs(0)))
+    }
+
+    /// Like `wait()`, but returns None instead of blocking if the runner has not yet
+    /// exited.
+    pub fn try_wait(&self) -> Option<Outcome> {
+        self.inner.state.wait_for(|s| s.exit.as_ref(), Some(Duration::from_secs(0)))
+    }
+
+    /// Like `ready()`, but gives up once the timeout has elapsed.
+    ///
+    /// Returns `ProcessError::Timeout` if the runner has not become ready in time,
+    /// in which case this or `ready()` may be called again.
+    pub fn ready_timeout(&self, timeout: Duration) -> Outcome {
+        self.inner.state.wait_for(|s| s.ready.as_ref(), Some(timeout))
+            .unwrap_or(Err(ProcessError::Timeout))
+    }
+
+    /// Like `wait()`, but gives up once the timeout has elapsed.
+    ///
+    /// Returns `ProcessError::Timeout` if the runner has not exited in time, in which
+    /// case this or `wait()` may be called again.
+    pub fn wait_timeout(&self, timeout: Duration) -> Outcome {
+        self.inner.state.wait_for(|s| s.exit.as_ref(), Some(timeout))
+            .unwrap_or(Err(ProcessError::Timeout))
+    }
+
+    /// Shuts down the runner following the Escalation, blocking until it has exited.
+    ///
+    /// Returns `ProcessError::StillRunning` if the runner has not exited by the
+    /// Escalation's deadline. Otherwise returns the same result as `wait()`.
+    pub fn shutdown(&self, escalation: Escalation<M>) -> Outcome {
+        self.signal(escalation.shutdown);
+        match self.wait_timeout(escalation.grace) {
+            Err(ProcessError::Timeout) => {},
+            res => return res,
+        }
+
+        self.signal(escalation.force);
+        match self.wait_timeout(escalation.deadline) {
+            Err(ProcessError::Timeout) => Err(ProcessError::StillRunning),
+            res => res,
         }
     }
 
-    fn spawn_run_thread(mut runner: Box<Runner + Send>,
-                           recv: Receiver<Signal>,
-                           setup: mpsc::Sender<Result<(), ProcessError<MaridError>>>,
-                           run: mpsc::Sender<Result<(), ProcessError<MaridError>>>)
+    fn spawn_run_thread(mut runner: Box<Runner<M> + Send>,
+                           recv: Receiver<M>,
+                           state: Arc<State>,
+                           events: Events<M>)
         -> thread::JoinHandle<()> {
         thread::spawn(move || {
-            let res = runner.setup().map_err(ProcessError::RunnerError);
-            let is_err = res.is_err();
-            setup.send(res).expect("Could not send setup result");
-
-            if !is_err {
-                let err = runner.run(recv).map_err(ProcessError::RunnerError);
-                run.send(err).expect("Could not send run result");
+            events.emit(EventKind::SetupStarted);
+            let res = catch_runner(|| runner.setup());
+            events.emit(outcome_event(&res, EventKind::SetupFinished));
+            let res = flatten(res);
+            if res.is_err() {
+                // The runner will never run, so waiters receive the setup error.
+                state.update(|s| {
+                    s.status = exited(&res);
+                    s.ready = Some(res.clone());
+                    s.exit = Some(res);
+                });
+                return
             }
+
+            state.update(|s| s.status = Status::Running);
+            events.emit(EventKind::Running);
+            let ready_state = state.clone();
+            let ready_events = events.clone();
+            let ready = Ready::new(move || {
+                ready_events.emit(EventKind::Ready);
+                ready_state.update(|s| {
+                    if s.exit.is_none() {
+                        s.status = Status::Ready;
+                        s.ready = Some(Ok(()));
+                    }
+                })
+            });
+            let res = catch_runner(move || runner.run_ready(recv, ready));
+            events.emit(outcome_event(&res, EventKind::Exited));
+            let res = flatten(res);
+            state.update(|s| {
+                s.status = exited(&res);
+                if s.ready.is_none() {
+                    s.ready = Some(res.clone());
+                }
+                s.exit = Some(res);
+            });
         })
     }
 }
 
+impl State {
+    fn update<F>(&self, func: F) where F: FnOnce(&mut Shared) {
+        func(&mut self.shared.lock().unwrap());
+        self.changed.notify_all();
+    }
 
-impl Process for MaridProcess {
-    type Error = ProcessError<MaridError>;
-
-    fn ready(&self) -> Result<(), Self::Error> {
-        match self.state.get() {
-            ProcState::Init => {
-                self.state.set(ProcState::SetupDone);
-                self.setup_chan.recv().unwrap_or(Err(ProcessError::CouldNotRecvResult))
-            },
-            _ => {
-                Err(ProcessError::ResultAlreadyGiven)
+    // Blocks until the picked result is available, returning None if the timeout
+    // elapses first.
+    fn wait_for<F>(&self, pick: F, timeout: Option<Duration>) -> Option<Outcome>
+        where F: Fn(&Shared) -> Option<&Outcome> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut shared = self.shared.lock().unwrap();
+        loop {
+            if let Some(res) = pick(&shared) {
+                return Some(res.clone())
             }
+            shared = match deadline {
+                None => self.changed.wait(shared).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None
+                    }
+                    self.changed.wait_timeout(shared, deadline - now).unwrap().0
+                },
+            };
         }
     }
+}
+
+// Shares the result of setup() or run(), reporting a panic as such.
+fn flatten(res: Result<(), MaridError>) -> Outcome {
+    res.map_err(|e| match e.downcast::<PanicError>() {
+        Ok(panic) => ProcessError::RunnerPanicked(panic.message),
+        Err(e) => ProcessError::RunnerError(Arc::new(e)),
+    })
+}
+
+fn exited(res: &Outcome) -> Status {
+    match *res {
+        Ok(()) => Status::Exited(Ok(())),
+        Err(ProcessError::RunnerError(ref e)) => Status::Exited(Err(e.to_string())),
+        Err(_) => Status::Panicked,
+    }
+}
+
+impl<M> Process<M> for MaridProcess<M> where M: Clone + fmt::Debug + Send + 'static {
+    type Error = ProcessError<SharedError>;
+
+    fn ready(&self) -> Result<(), Self::Error> {
+        self.inner.state.wait_for(|s| s.ready.as_ref(), None)
+            .expect("Wait without a timeout returned early")
+    }
 
     fn wait(&self) -> Result<(), Self::Error> {
-        match self.state.get() {
-            ProcState::Init | ProcState::SetupDone => {
-                self.state.set(ProcState::Finished);
-                self.run_chan.recv().unwrap_or(Err(ProcessError::CouldNotRecvResult))
-            },
-            ProcState::Finished => {
-                Err(ProcessError::ResultAlreadyGiven)
-            }
-        }
+        self.inner.state.wait_for(|s| s.exit.as_ref(), None)
+            .expect("Wait without a timeout returned early")
     }
 
-    fn signal(&self, signal: Signal) {
-        self.signaler.send(signal)
+    fn signal(&self, signal: M) {
+        self.inner.events.emit(EventKind::SignalDelivered(signal.clone()));
+        self.inner.signaler.send(signal)
     }
 }
 
-impl Drop for MaridProcess {
+impl<M> Drop for Inner<M> {
     fn drop(&mut self) {
-        let runner = self.runner.take();
-        runner.expect("No runner").
-            join().expect("Runner panicked");
+        let runner = self.runner.take().expect("No runner");
+        let policy = mem::replace(&mut *self.drop_policy.lock().unwrap(), DropPolicy::Detach);
+        let exited = match policy {
+            DropPolicy::Detach => false,
+            DropPolicy::Join => true,
+            DropPolicy::SignalAndJoin(signal, timeout) => {
+                self.signaler.send(signal);
+                self.state.wait_for(|s| s.exit.as_ref(), Some(timeout)).is_some()
+            },
+        };
+        // Panics are caught on the runner's thread, so there is nothing to report here.
+        if exited {
+            let _ = runner.join();
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use test_helpers::{TestRunner};
-    use super::{MaridProcess, ProcessError};
-    use traits::{Runner, Process, Signal};
+    use test_helpers::{TestRunner, TestError};
+    use super::{MaridProcess, ProcessError, Status, DropPolicy};
+    use traits::{Runner, Process, Signal, Receiver};
+    use {MaridError, FnRunner, Escalation, Ready, Composer, Event, EventKind};
     use chan;
+    use std::sync::{Arc, mpsc};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    struct FailingSetup;
+
+    impl Runner for FailingSetup {
+        fn setup(&mut self) -> Result<(), MaridError> {
+            Err(Box::new(TestError))
+        }
+
+        fn run(self: Box<Self>, _signals: Receiver<Signal>) -> Result<(), MaridError> {
+            unreachable!()
+        }
+    }
+
+    // Blocks in setup until released.
+    struct SlowSetup {
+        release: mpsc::Receiver<()>,
+    }
+
+    impl Runner for SlowSetup {
+        fn setup(&mut self) -> Result<(), MaridError> {
+            let _ = self.release.recv();
+            Ok(())
+        }
+
+        fn run(self: Box<Self>, signals: Receiver<Signal>) -> Result<(), MaridError> {
+            signals.recv();
+            Ok(())
+        }
+    }
+
+    // Reports ready from inside run() once released, failing if the release is dropped.
+    struct LateReady {
+        release: mpsc::Receiver<()>,
+    }
+
+    impl Runner for LateReady {
+        fn setup(&mut self) -> Result<(), MaridError> {
+            Ok(())
+        }
+
+        fn run(self: Box<Self>, signals: Receiver<Signal>) -> Result<(), MaridError> {
+            self.run_ready(signals, Ready::unobserved())
+        }
+
+        fn run_ready(self: Box<Self>, signals: Receiver<Signal>, ready: Ready) -> Result<(), MaridError> {
+            try!(self.release.recv().map_err(|_| Box::new(TestError) as MaridError));
+            ready.close();
+            signals.recv();
+            Ok(())
+        }
+    }
 
     #[test]
     fn test_ready_process() {
@@ -175,13 +465,9 @@ mod tests {
         let res = process.ready();
         assert!(res.is_ok());
 
+        // Every call receives the result.
         let res = process.ready();
-        assert!(res.is_err());
-        match res {
-            Ok(_) => unreachable!(),
-            Err(ProcessError::ResultAlreadyGiven) => {},
-            _ => assert!(false, "Wrong error type"),
-        }
+        assert!(res.is_ok());
 
         // Finish workflow
         process.signal(Signal::INT);
@@ -202,12 +488,346 @@ mod tests {
         let res = process.wait();
         assert!(res.is_ok());
 
+        // Every call receives the result.
         let res = process.wait();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_ready_timeout() {
+        let (release_sn, release_rc) = mpsc::channel();
+        let runner = Box::new(SlowSetup{ release: release_rc }) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        match process.ready_timeout(Duration::from_millis(10)) {
+            Err(ProcessError::Timeout) => {},
+            _ => assert!(false, "Expected a timeout"),
+        }
+
+        release_sn.send(()).unwrap();
+        assert!(process.ready_timeout(Duration::from_secs(5)).is_ok());
+
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+    }
+
+    #[test]
+    fn test_wait_timeout() {
+        let (sn, rc) = chan::sync(0);
+        let runner = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+        match process.wait_timeout(Duration::from_millis(10)) {
+            Err(ProcessError::Timeout) => {},
+            _ => assert!(false, "Expected a timeout"),
+        }
+
+        process.signal(Signal::INT);
+        assert!(rc.recv().unwrap());
+        assert!(process.wait_timeout(Duration::from_secs(5)).is_ok());
+        assert!(process.wait_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn test_ready_from_run() {
+        let (release_sn, release_rc) = mpsc::channel();
+        let runner = Box::new(LateReady{ release: release_rc }) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        match process.ready_timeout(Duration::from_millis(10)) {
+            Err(ProcessError::Timeout) => {},
+            _ => assert!(false, "Expected a timeout"),
+        }
+        assert_eq!(process.status(), Status::Running);
+
+        release_sn.send(()).unwrap();
+        assert!(process.ready().is_ok());
+        assert_eq!(process.status(), Status::Ready);
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+    }
+
+    #[test]
+    fn test_exit_before_ready() {
+        let (release_sn, release_rc) = mpsc::channel::<()>();
+        let runner = Box::new(LateReady{ release: release_rc }) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        drop(release_sn);
+        match process.ready() {
+            Err(ProcessError::RunnerError(e)) => assert!(e.is::<TestError>()),
+            _ => assert!(false, "Expected a runner error"),
+        }
+    }
+
+    #[test]
+    fn test_try_ready_and_status() {
+        let (release_sn, release_rc) = mpsc::channel();
+        let runner = Box::new(SlowSetup{ release: release_rc }) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        assert_eq!(process.status(), Status::Initializing);
+        assert!(process.try_ready().is_none());
+
+        release_sn.send(()).unwrap();
+        assert!(process.ready().is_ok());
+        assert!(process.try_wait().is_none());
+
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+        assert_eq!(process.status(), Status::Exited(Ok(())));
+        match process.try_wait() {
+            Some(Ok(())) => {},
+            _ => assert!(false, "Expected the result"),
+        }
+    }
+
+    #[test]
+    fn test_try_wait_and_error_status() {
+        let (sn, rc) = chan::sync(0);
+        let runner = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+
+        process.signal(Signal::HUP);
+        assert!(!rc.recv().unwrap());
+        // The runner may still be sending its result.
+        let res = loop {
+            if let Some(res) = process.try_wait() {
+                break res
+            }
+            thread::yield_now();
+        };
         assert!(res.is_err());
-        match res {
-            Ok(_) => unreachable!(),
-            Err(ProcessError::ResultAlreadyGiven) => {},
+        assert_eq!(process.status(), Status::Exited(Err("a testing error".to_string())));
+    }
+
+    #[test]
+    fn test_runner_panic() {
+        let runner = Box::new(FnRunner::new(|_sigs| {
+            panic!("runner exploded")
+        })) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+        match process.wait() {
+            Err(ProcessError::RunnerPanicked(ref msg)) if msg == "runner exploded" => {},
             _ => assert!(false, "Wrong error type"),
         }
+        assert_eq!(process.status(), Status::Panicked);
+    }
+
+    #[test]
+    fn test_shared_process() {
+        fn assert_shareable<T: Clone + Send + Sync>(_: &T) {}
+
+        let (sn, rc) = chan::sync(0);
+        let runner = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        assert_shareable(&process);
+
+        let waiters: Vec<_> = (0..3).map(|_| {
+            let process = process.clone();
+            thread::spawn(move || {
+                assert!(process.ready().is_ok());
+                process.wait().is_err()
+            })
+        }).collect();
+
+        process.signal(Signal::HUP);
+        assert!(!rc.recv().unwrap());
+        for waiter in waiters {
+            assert!(waiter.join().unwrap());
+        }
+        assert!(process.wait().is_err());
+    }
+
+    #[test]
+    fn test_wait_after_setup_error() {
+        let runner = Box::new(FailingSetup) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        assert!(process.ready().is_err());
+        match process.wait() {
+            Err(ProcessError::RunnerError(e)) => assert_eq!(e.to_string(), "a testing error"),
+            _ => assert!(false, "Wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_drop_detaches_by_default() {
+        let (sn, rc) = chan::sync(1);
+        let runner = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn.clone(), signal_rc);
+        assert!(process.ready().is_ok());
+        // Would block forever if the runner's thread were joined.
+        drop(process);
+
+        signal_sn.send(Signal::INT);
+        assert!(rc.recv().unwrap());
+    }
+
+    #[test]
+    fn test_drop_signal_and_join() {
+        let (sn, rc) = chan::sync(1);
+        let runner = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        process.set_drop_policy(DropPolicy::SignalAndJoin(Signal::INT, Duration::from_secs(5)));
+        assert!(process.ready().is_ok());
+        drop(process);
+
+        // The runner has already exited.
+        assert!(rc.recv().unwrap());
+    }
+
+    #[test]
+    fn test_drop_join() {
+        let done = Arc::new(AtomicBool::new(false));
+        let done_clone = done.clone();
+        let (release_sn, release_rc) = mpsc::channel();
+        let runner = Box::new(FnRunner::new(move |_sigs| {
+            release_rc.recv().unwrap();
+            done_clone.store(true, Ordering::SeqCst);
+            Ok(())
+        })) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        process.set_drop_policy(DropPolicy::Join);
+        let clone = process.clone();
+        // Would block forever if the runner's thread were joined.
+        drop(process);
+        assert!(!done.load(Ordering::SeqCst));
+
+        // Only the last handle joins the runner.
+        release_sn.send(()).unwrap();
+        drop(clone);
+        assert!(done.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_shutdown_escalation() {
+        let runner = Box::new(FnRunner::new(|signals: Receiver<Signal>| {
+            while signals.recv() != Some(Signal::KILL) {}
+            Ok(())
+        })) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+
+        let escalation = Escalation::new(Signal::INT, Duration::from_millis(10),
+                                         Signal::KILL, Duration::from_secs(5));
+        assert!(process.shutdown(escalation).is_ok());
+    }
+
+    #[test]
+    fn test_shutdown_still_running() {
+        let (_release_sn, release_rc) = mpsc::channel::<()>();
+        let runner = Box::new(FnRunner::new(move |_signals| {
+            let _ = release_rc.recv();
+            Ok(())
+        })) as Box<Runner + Send>;
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(runner, signal_sn, signal_rc);
+
+        let escalation = Escalation::new(Signal::INT, Duration::from_millis(10),
+                                         Signal::KILL, Duration::from_millis(10));
+        match process.shutdown(escalation) {
+            Err(ProcessError::StillRunning) => {},
+            _ => assert!(false, "Wrong error type"),
+        }
+        assert_eq!(process.status(), Status::Ready);
+    }
+
+    #[test]
+    fn test_process_events() {
+        let (sn, _rc) = chan::sync(1);
+        let runner = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+        let composer = Composer::new(vec!(), Signal::INT).member("test", runner);
+        let events = composer.subscribe();
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(Box::new(composer), signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+
+        let (root, members): (Vec<Event>, Vec<Event>) = events.try_iter().partition(|e| e.index.is_none());
+        let root: Vec<EventKind> = root.into_iter().map(|e| e.kind).collect();
+        assert_eq!(root, vec!(EventKind::SetupStarted,
+                              EventKind::SetupFinished(Ok(())),
+                              EventKind::Running,
+                              EventKind::Ready,
+                              EventKind::SignalDelivered(Signal::INT),
+                              EventKind::Exited(Ok(()))));
+        assert!(members.iter().all(|e| e.name == Some("test".to_string())));
+        assert_eq!(members.last().map(|e| e.kind.clone()), Some(EventKind::Exited(Ok(()))));
+    }
+
+    #[test]
+    fn test_process_metrics() {
+        let (sn, _rc) = chan::sync(1);
+        let runner = Box::new(TestRunner::new(0, sn)) as Box<Runner + Send>;
+        let composer = Composer::new(vec!(), Signal::INT).member("test", runner);
+        let early = composer.metrics();
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(Box::new(composer), signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+
+        let snapshot = process.metrics().snapshot();
+        assert_eq!(snapshot, early.snapshot());
+        assert_eq!(snapshot.len(), 2);
+        for runner in &snapshot {
+            assert!(runner.setup_duration.is_some());
+            assert!(runner.time_to_ready.is_some());
+            assert!(runner.uptime.is_some());
+            assert_eq!(runner.exits.ok, 1);
+            assert_eq!(runner.signals.get("INT"), Some(&1));
+        }
+        assert_eq!(snapshot[1].name, Some("test".to_string()));
+    }
+
+    #[test]
+    fn test_process_metrics_nested() {
+        fn waiting() -> Box<Runner + Send> {
+            Box::new(FnRunner::new(|signals: Receiver<Signal>| {
+                signals.recv();
+                Ok(())
+            }))
+        }
+        let inner = Box::new(Composer::new(vec!(waiting()), Signal::INT)) as Box<Runner + Send>;
+        let outer = Composer::new(vec!(waiting()), Signal::INT).member("inner", inner);
+
+        let (signal_sn, signal_rc) = chan::sync(9);
+        let process = MaridProcess::start(Box::new(outer), signal_sn, signal_rc);
+        assert!(process.ready().is_ok());
+        process.signal(Signal::INT);
+        assert!(process.wait().is_ok());
+
+        // The first members of both Composers are kept apart.
+        let mut paths: Vec<String> = process.metrics().snapshot().into_iter().map(|m| m.path).collect();
+        paths.sort();
+        assert_eq!(paths, vec!("inner", "inner/member 0", "member 0", "process"));
     }
 }
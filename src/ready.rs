@@ -0,0 +1,55 @@
+use thunk::Thunk;
+
+/// A handle closed by a runner once it is ready, given to `Runner::run_ready()`.
+///
+/// Dropping the handle without closing it means the runner never became ready. Whoever
+/// is waiting on it is then given the runner's result once it exits.
+pub struct Ready {
+    notify: Option<Thunk<'static>>,
+}
+
+impl Ready {
+    /// Creates a Ready handle that calls `notify` once it is closed.
+    pub fn new<F>(notify: F) -> Ready where F: FnOnce() + Send + 'static {
+        Ready {
+            notify: Some(Thunk::with_arg(move |()| notify())),
+        }
+    }
+
+    /// Creates a Ready handle that nothing is waiting on.
+    pub fn unobserved() -> Ready {
+        Ready {
+            notify: None,
+        }
+    }
+
+    /// Reports the runner as ready.
+    pub fn close(mut self) {
+        if let Some(notify) = self.notify.take() {
+            notify.invoke(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ready;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_ready_close() {
+        let (sn, rc) = mpsc::channel();
+        let ready = Ready::new(move || sn.send(()).unwrap());
+        assert!(rc.try_recv().is_err());
+        ready.close();
+        assert!(rc.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_ready_dropped() {
+        let (sn, rc) = mpsc::channel::<()>();
+        let ready = Ready::new(move || sn.send(()).unwrap());
+        drop(ready);
+        assert!(rc.recv().is_err());
+    }
+}
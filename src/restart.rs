@@ -0,0 +1,468 @@
+use traits::{Runner, Signal, Receiver};
+use panic::catch_runner;
+use ready::Ready;
+use events::{Events, EventKind, outcome_event};
+use composer::setup_member;
+use {MaridError};
+use chan;
+use std::thread;
+use std::cmp;
+use std::fmt;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+// The window used when no limit is given to a Restart.
+const DEFAULT_WINDOW_SECS: u64 = 60;
+
+/// Determines how long a Restart waits before starting a new runner.
+///
+/// The delay grows with the number of restarts that happened within the Restart's
+/// window, and starts over once a runner has run for longer than the window.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Backoff {
+    /// Wait the same amount of time before every restart.
+    Fixed(Duration),
+    /// Wait `initial`, doubling with every recent restart, up to `max`.
+    Exponential {
+        /// Delay before the first restart.
+        initial: Duration,
+        /// Upper bound of the delay.
+        max: Duration,
+    },
+    /// Wait a random amount of time between zero and the `Exponential` delay.
+    Jittered {
+        /// Delay bound before the first restart.
+        initial: Duration,
+        /// Upper bound of the delay.
+        max: Duration,
+    },
+}
+
+impl Backoff {
+    /// Returns the delay before the restart following `restarts` recent restarts.
+    pub fn delay(&self, restarts: usize) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => exponential(initial, max, restarts),
+            Backoff::Jittered { initial, max } => jitter(exponential(initial, max, restarts)),
+        }
+    }
+}
+
+fn exponential(initial: Duration, max: Duration, restarts: usize) -> Duration {
+    let factor = 1u32.checked_shl(restarts as u32).unwrap_or(0);
+    match initial.checked_mul(factor) {
+        Some(delay) if factor > 0 => cmp::min(delay, max),
+        _ => max,
+    }
+}
+
+fn jitter(delay: Duration) -> Duration {
+    let nanos = delay.as_secs() * 1_000_000_000 + delay.subsec_nanos() as u64;
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(nanos);
+    Duration::from_nanos(hasher.finish() % (nanos + 1))
+}
+
+/// A Runner that restarts a failing runner.
+///
+/// A new runner is built by the factory, setup and run whenever the current one fails,
+/// after waiting as determined by the Backoff. Signals are forwarded to the current
+/// runner. Once the shutdown signal has been received, the Restart is shutting down and
+/// the runner's result is returned without restarting it. Receiving the shutdown signal
+/// while waiting to restart returns Ok, and other signals received meanwhile are dropped.
+///
+/// When more than the limit of restarts happen within the window, the Restart gives up
+/// and returns the last error. By default there is no limit, and the window is a minute.
+///
+/// The Restart is ready once any of its runners first reports ready, so that a runner
+/// failing before it is ready delays it until one of its replacements is.
+///
+/// Whenever a new runner replaces a failed one, the Restart emits the `Exited` event of
+/// the failed runner, the setup events of the new one, then `Restarted`, `Running` and
+/// `Ready` events as the new runner runs. Every runner is thus counted by the Metrics.
+/// The events of the first runner, and the exit of the last one, are emitted by the
+/// MaridProcess or group running the Restart.
+pub struct Restart<F, M = Signal> {
+    factory: F,
+    backoff: Backoff,
+    shutdown_signal: M,
+    limit: Option<usize>,
+    window: Duration,
+    runner: Option<Box<Runner<M> + Send>>,
+    events: Events<M>,
+}
+
+enum Exit {
+    Done(Result<(), MaridError>),
+    Failed(MaridError),
+}
+
+impl<F, M> Restart<F, M> where F: FnMut() -> Box<Runner<M> + Send> + Send, M: Clone + PartialEq + fmt::Debug + Send + 'static {
+    /// Creates a new Restart, building runners with the factory.
+    ///
+    /// The shutdown_signal is the signal after which a failing runner is no longer
+    /// restarted.
+    pub fn new(factory: F, backoff: Backoff, shutdown_signal: M) -> Restart<F, M> {
+        Restart {
+            factory: factory,
+            backoff: backoff,
+            shutdown_signal: shutdown_signal,
+            limit: None,
+            window: Duration::from_secs(DEFAULT_WINDOW_SECS),
+            runner: None,
+            events: Events::new(),
+        }
+    }
+
+    /// Gives up once more than `max_restarts` restarts have happened within the window.
+    pub fn limit(mut self, max_restarts: usize, window: Duration) -> Restart<F, M> {
+        self.limit = Some(max_restarts);
+        self.window = window;
+        self
+    }
+
+    // Builds and sets up a new runner, emitting its setup events if it replaces another.
+    fn incarnate(&mut self, replacing: bool) -> Result<Box<Runner<M> + Send>, MaridError> {
+        let mut runner = (self.factory)();
+        runner.observe(self.events.clone());
+        if replacing {
+            try!(setup_member(&mut runner, &self.events));
+        } else {
+            try!(catch_runner(|| runner.setup()));
+        }
+        Ok(runner)
+    }
+
+    // Emits the exit of a failed runner that is about to be replaced, returning its error.
+    fn replaced(&self, err: MaridError) -> MaridError {
+        let res = Err(err);
+        self.events.emit(outcome_event(&res, EventKind::Exited));
+        res.unwrap_err()
+    }
+
+    // Records a restart, forgetting those older than the window, and returning false if
+    // the limit has been reached.
+    fn may_restart(&self, restarts: &mut VecDeque<Instant>) -> bool {
+        let now = Instant::now();
+        while restarts.front().map_or(false, |t| now.duration_since(*t) > self.window) {
+            restarts.pop_front();
+        }
+        if self.limit.map_or(false, |max_restarts| restarts.len() >= max_restarts) {
+            return false
+        }
+        restarts.push_back(now);
+        true
+    }
+}
+
+// Runs the runner on its own thread, forwarding signals to it. Once the signal channel
+// has been closed, it is replaced by the never ready `closed` channel.
+fn run_incarnation<M>(runner: Box<Runner<M> + Send>, signals: &mut Receiver<M>, closed: &Receiver<M>,
+                      shutdown: &M, ready: Ready) -> Exit
+    where M: PartialEq + Send + 'static {
+    let (sig_sn, sig_rc) = chan::sync(1024);
+    let (exit_sn, exit_rc) = chan::sync(1);
+    thread::spawn(move || {
+        exit_sn.send(catch_runner(move || runner.run_ready(sig_rc, ready)));
+    });
+
+    let mut shutting_down = false;
+    loop {
+        let sig;
+        chan_select! {
+            signals.recv() -> s => sig = s,
+            exit_rc.recv() -> res => {
+                return match res.expect("Runner thread went away") {
+                    Err(e) if !shutting_down => Exit::Failed(e),
+                    res => Exit::Done(res),
+                }
+            },
+        }
+        match sig {
+            Some(sig) => {
+                shutting_down = shutting_down || sig == *shutdown;
+                sig_sn.send(sig);
+            },
+            None => *signals = closed.clone(),
+        }
+    }
+}
+
+// Waits for the delay, returning true if the shutdown signal was received meanwhile.
+// Other signals are dropped, as there is no runner to deliver them to.
+fn backoff<M>(delay: Duration, signals: &mut Receiver<M>, closed: &Receiver<M>, shutdown: &M) -> bool
+    where M: PartialEq {
+    let timer = chan::after(delay);
+    loop {
+        let sig;
+        chan_select! {
+            signals.recv() -> s => sig = s,
+            timer.recv() => return false,
+        }
+        match sig {
+            Some(ref sig) if sig == shutdown => return true,
+            Some(_) => {},
+            None => *signals = closed.clone(),
+        }
+    }
+}
+
+impl<F, M> Runner<M> for Restart<F, M> where F: FnMut() -> Box<Runner<M> + Send> + Send, M: Clone + PartialEq + fmt::Debug + Send + 'static {
+    fn run(self: Box<Self>, signals: Receiver<M>) -> Result<(), MaridError> {
+        self.run_ready(signals, Ready::unobserved())
+    }
+
+    fn run_ready(mut self: Box<Self>, signals: Receiver<M>, ready: Ready) -> Result<(), MaridError> {
+        // Closed by the first runner to report ready.
+        let ready = Arc::new(Mutex::new(Some(ready)));
+        let mut runner = match self.runner.take() {
+            Some(runner) => runner,
+            None => try!(self.incarnate(false)),
+        };
+        let mut restarts = VecDeque::new();
+        let mut signals = signals;
+        // Swapped in for the signal channel once it has been closed.
+        let (_closed_sn, closed) = chan::sync(0);
+
+        loop {
+            let outer = ready.clone();
+            let ready_events = self.events.clone();
+            let ready = Ready::new(move || {
+                match outer.lock().unwrap_or_else(|e| e.into_inner()).take() {
+                    Some(ready) => ready.close(),
+                    // Reported by the runner running the Restart when first closed.
+                    None => ready_events.emit(EventKind::Ready),
+                }
+            });
+            let mut err = match run_incarnation(runner, &mut signals, &closed, &self.shutdown_signal, ready) {
+                Exit::Done(res) => return res,
+                Exit::Failed(e) => e,
+            };
+            // Setup failures of the replacements have been emitted as such.
+            let mut ran = true;
+
+            loop {
+                if !self.may_restart(&mut restarts) {
+                    return Err(err)
+                }
+                if ran {
+                    err = self.replaced(err);
+                    ran = false;
+                }
+                let delay = self.backoff.delay(restarts.len() - 1);
+                if backoff(delay, &mut signals, &closed, &self.shutdown_signal) {
+                    return Ok(())
+                }
+                match self.incarnate(true) {
+                    Ok(r) => {
+                        self.events.emit(EventKind::Restarted);
+                        self.events.emit(EventKind::Running);
+                        runner = r;
+                        break
+                    },
+                    Err(e) => err = e,
+                }
+            }
+        }
+    }
+
+    fn observe(&mut self, events: Events<M>) {
+        self.events = events;
+    }
+
+    fn setup(&mut self) -> Result<(), MaridError> {
+        let runner = try!(self.incarnate(false));
+        self.runner = Some(runner);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Restart, Backoff, backoff};
+    use test_helpers::{TestRunner, TestError};
+    use {Runner, FnRunner, Signal, Receiver, MaridError, Events, EventKind, Ready};
+    use chan;
+    use std::sync::{Arc, mpsc};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    fn failing(count: Arc<AtomicUsize>, failures: usize) -> Box<Runner + Send> {
+        let n = count.fetch_add(1, Ordering::SeqCst);
+        Box::new(FnRunner::new(move |_sigs| {
+            if n < failures {
+                Err(Box::new(TestError) as MaridError)
+            } else {
+                Ok(())
+            }
+        }))
+    }
+
+    #[test]
+    fn test_restart_until_success() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let factory_count = count.clone();
+        let mut restart = Box::new(Restart::new(move || failing(factory_count.clone(), 2),
+                                                Backoff::Fixed(Duration::from_millis(1)), Signal::INT));
+
+        let (_sn, rc) = chan::sync(1);
+        assert!(restart.setup().is_ok());
+        assert!(restart.run(rc).is_ok());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_restart_gives_up() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let factory_count = count.clone();
+        let restart = Box::new(Restart::new(move || failing(factory_count.clone(), 10),
+                                            Backoff::Fixed(Duration::from_millis(1)), Signal::INT)
+                               .limit(2, Duration::from_secs(60)));
+
+        let (_sn, rc) = chan::sync(1);
+        assert!(restart.run(rc).is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_restart_events() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let factory_count = count.clone();
+        let mut restart = Box::new(Restart::new(move || failing(factory_count.clone(), 1),
+                                                Backoff::Fixed(Duration::from_millis(1)), Signal::INT));
+        let events = Events::new();
+        let rc = events.subscribe();
+        let metrics = events.metrics();
+        restart.observe(events);
+
+        let (_sn, sig_rc) = chan::sync(1);
+        assert!(restart.setup().is_ok());
+        assert!(restart.run(sig_rc).is_ok());
+        // The first runner reported the Restart ready, its replacement reports its own.
+        let kinds: Vec<EventKind> = rc.try_iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec!(EventKind::Exited(Err("a testing error".to_string())),
+                               EventKind::SetupStarted,
+                               EventKind::SetupFinished(Ok(())),
+                               EventKind::Restarted,
+                               EventKind::Running,
+                               EventKind::Ready));
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot[0].exits.failed, snapshot[0].restarts), (1, 1));
+    }
+
+    #[test]
+    fn test_restart_forwards_signals() {
+        let (sn, rc) = chan::sync(1);
+        let mut restart = Box::new(Restart::new(move || {
+            Box::new(TestRunner::new(0, sn.clone())) as Box<Runner + Send>
+        }, Backoff::Fixed(Duration::from_millis(1)), Signal::INT));
+        let events = Events::new();
+        let restarted = events.subscribe();
+        restart.observe(events);
+
+        let (sig_sn, sig_rc) = chan::sync(1);
+        let handle = thread::spawn(move || restart.run(sig_rc));
+        // The runner fails on HUP and is restarted, as HUP is not the shutdown signal.
+        sig_sn.send(Signal::HUP);
+        assert!(!rc.recv().unwrap());
+        while restarted.recv().unwrap().kind != EventKind::Restarted {}
+        sig_sn.send(Signal::INT);
+        assert!(handle.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_restart_shutdown_failure() {
+        let (sn, rc) = chan::sync(1);
+        let restart = Box::new(Restart::new(move || {
+            Box::new(TestRunner::new(0, sn.clone())) as Box<Runner + Send>
+        }, Backoff::Fixed(Duration::from_millis(1)), Signal::HUP));
+
+        let (sig_sn, sig_rc) = chan::sync(1);
+        sig_sn.send(Signal::HUP);
+        // The runner fails on HUP, but is not restarted after the shutdown signal.
+        assert!(restart.run(sig_rc).is_err());
+        assert!(!rc.recv().unwrap());
+    }
+
+    #[test]
+    fn test_backoff_signals() {
+        let (_closed_sn, closed) = chan::sync(0);
+        let (sig_sn, mut sig_rc) = chan::sync(2);
+        sig_sn.send(Signal::HUP);
+        sig_sn.send(Signal::INT);
+        // HUP is dropped, and INT ends the wait.
+        assert!(backoff(Duration::from_secs(60), &mut sig_rc, &closed, &Signal::INT));
+
+        // Waits for the delay once the signal channel has been closed.
+        drop(sig_sn);
+        assert!(!backoff(Duration::from_millis(1), &mut sig_rc, &closed, &Signal::INT));
+    }
+
+    // Fails before reporting ready when `fail` is set, and otherwise runs until signaled.
+    struct LateReady {
+        fail: bool,
+    }
+
+    impl Runner for LateReady {
+        fn setup(&mut self) -> Result<(), MaridError> {
+            Ok(())
+        }
+
+        fn run(self: Box<Self>, signals: Receiver<Signal>) -> Result<(), MaridError> {
+            self.run_ready(signals, Ready::unobserved())
+        }
+
+        fn run_ready(self: Box<Self>, signals: Receiver<Signal>, ready: Ready) -> Result<(), MaridError> {
+            if self.fail {
+                return Err(Box::new(TestError))
+            }
+            ready.close();
+            signals.recv();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_restart_ready() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let factory_count = count.clone();
+        let restart = Box::new(Restart::new(move || {
+            let n = factory_count.fetch_add(1, Ordering::SeqCst);
+            Box::new(LateReady { fail: n == 0 }) as Box<Runner + Send>
+        }, Backoff::Fixed(Duration::from_millis(1)), Signal::INT));
+
+        let (ready_sn, ready_rc) = mpsc::channel();
+        let ready = Ready::new(move || ready_sn.send(()).unwrap());
+        let (sig_sn, sig_rc) = chan::sync(1);
+        let handle = thread::spawn(move || restart.run_ready(sig_rc, ready));
+
+        // The second runner reports the Restart ready.
+        ready_rc.recv_timeout(Duration::from_secs(5)).expect("Restart did not become ready");
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        sig_sn.send(Signal::INT);
+        assert!(handle.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let initial = Duration::from_millis(10);
+        let max = Duration::from_millis(50);
+        assert_eq!(Backoff::Fixed(initial).delay(5), initial);
+
+        let exponential = Backoff::Exponential { initial: initial, max: max };
+        assert_eq!(exponential.delay(0), initial);
+        assert_eq!(exponential.delay(2), Duration::from_millis(40));
+        assert_eq!(exponential.delay(3), max);
+        assert_eq!(exponential.delay(100), max);
+
+        let jittered = Backoff::Jittered { initial: initial, max: max };
+        for restarts in 0..10 {
+            assert!(jittered.delay(restarts) <= exponential.delay(restarts));
+        }
+    }
+}
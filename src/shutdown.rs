@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+use traits::Signal;
+
+/// An escalating shutdown of a runner.
+///
+/// The `shutdown` signal is sent first. Runners that have not exited once the `grace`
+/// period has elapsed are sent the `force` signal, and runners that are still running
+/// after a further `deadline` are given up on and reported as still running.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Escalation<M = Signal> {
+    /// The signal asking a runner to shut down.
+    pub shutdown: M,
+    /// How long to wait for runners to exit after the shutdown signal.
+    pub grace: Duration,
+    /// The signal sent to runners still running after the grace period.
+    pub force: M,
+    /// How long to wait for runners to exit after the force signal.
+    pub deadline: Duration,
+}
+
+impl<M> Escalation<M> {
+    /// Creates a new Escalation.
+    pub fn new(shutdown: M, grace: Duration, force: M, deadline: Duration) -> Escalation<M> {
+        Escalation {
+            shutdown: shutdown,
+            grace: grace,
+            force: force,
+            deadline: deadline,
+        }
+    }
+}
+
+/// Error reported for a runner that was still running once an Escalation gave up on it.
+///
+/// The runner's thread is left running in the background.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct StillRunning;
+
+impl fmt::Display for StillRunning {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Runner did not exit before the shutdown deadline")
+    }
+}
+
+impl Error for StillRunning {
+    fn description(&self) -> &str {
+        "Runner did not exit before the shutdown deadline"
+    }
+}
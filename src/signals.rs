@@ -0,0 +1,158 @@
+use traits::{Signal, Sender};
+use chan_signal;
+use std::sync::{Arc, Mutex};
+
+/// A source of signals for a launched runner.
+///
+/// `launch()` starts the source with a Sender, through which the source delivers
+/// signals for as long as it lives.
+pub trait SignalSource {
+    /// Starts delivering signals to the sender.
+    fn start(self: Box<Self>, sender: Sender<Signal>);
+}
+
+/// Signals received by the OS process, through `chan_signal`.
+///
+/// # Limitations
+///
+/// `chan_signal` blocks the signals in the thread that first starts a source, and in the
+/// threads it spawns afterwards. Threads spawned before keep the default handling, so a
+/// signal delivered to one of them may terminate the process instead of reaching the
+/// runner. An OsSignals must therefore be given to `launch()` before the process spawns
+/// any thread, which is not checked.
+///
+/// As with `chan_signal`, a signal arriving while the runner's channel is full is dropped.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct OsSignals {
+    signals: Vec<Signal>,
+}
+
+impl OsSignals {
+    /// Creates a source of the given OS signals.
+    pub fn new(signals: Vec<Signal>) -> OsSignals {
+        OsSignals {
+            signals: signals,
+        }
+    }
+}
+
+impl SignalSource for OsSignals {
+    fn start(self: Box<Self>, sender: Sender<Signal>) {
+        for sig in self.signals {
+            chan_signal::notify_on(&sender, sig);
+        }
+    }
+}
+
+/// A list of signals is an `OsSignals` source.
+impl SignalSource for Vec<Signal> {
+    fn start(self: Box<Self>, sender: Sender<Signal>) {
+        Box::new(OsSignals::new(*self)).start(sender)
+    }
+}
+
+/// Signals raised by hand, for example by a test.
+///
+/// Clones share the same subscribers, so a clone kept aside can raise signals once the
+/// source has been given to `launch()`. Signals raised before the source is started are
+/// dropped, as are signals raised while a started copy's channel is full.
+#[derive(Clone, Default)]
+pub struct ManualSignals {
+    senders: Arc<Mutex<Vec<Sender<Signal>>>>,
+}
+
+impl ManualSignals {
+    /// Creates a new ManualSignals.
+    pub fn new() -> ManualSignals {
+        ManualSignals::default()
+    }
+
+    /// Delivers the signal to every started copy of this source, without blocking.
+    pub fn raise(&self, signal: Signal) {
+        for sender in self.senders.lock().unwrap().iter() {
+            chan_select! {
+                default => {},
+                sender.send(signal) => {},
+            }
+        }
+    }
+}
+
+impl SignalSource for ManualSignals {
+    fn start(self: Box<Self>, sender: Sender<Signal>) {
+        self.senders.lock().unwrap().push(sender);
+    }
+}
+
+/// Merges the signals of several sources.
+#[derive(Default)]
+pub struct MergedSignals {
+    sources: Vec<Box<SignalSource + Send>>,
+}
+
+impl MergedSignals {
+    /// Creates a MergedSignals without any source.
+    pub fn new() -> MergedSignals {
+        MergedSignals::default()
+    }
+
+    /// Adds a source, whose signals are delivered along with the others.
+    pub fn with<S>(mut self, source: S) -> MergedSignals
+        where S: SignalSource + Send + 'static {
+        self.sources.push(Box::new(source));
+        self
+    }
+}
+
+impl SignalSource for MergedSignals {
+    fn start(self: Box<Self>, sender: Sender<Signal>) {
+        for source in self.sources {
+            source.start(sender.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SignalSource, ManualSignals, MergedSignals};
+    use Signal;
+    use chan;
+
+    #[test]
+    fn test_manual_signals() {
+        let (sn, rc) = chan::sync(2);
+        let source = ManualSignals::new();
+        source.raise(Signal::HUP);
+        Box::new(source.clone()).start(sn);
+
+        source.raise(Signal::INT);
+        drop(source);
+        assert_eq!(rc.recv(), Some(Signal::INT));
+    }
+
+    #[test]
+    fn test_manual_signals_full() {
+        let (sn, rc) = chan::sync(1);
+        let source = ManualSignals::new();
+        Box::new(source.clone()).start(sn);
+
+        // The second signal is dropped rather than blocking.
+        source.raise(Signal::INT);
+        source.raise(Signal::TERM);
+        assert_eq!(rc.recv(), Some(Signal::INT));
+    }
+
+    #[test]
+    fn test_merged_signals() {
+        let (sn, rc) = chan::sync(2);
+        let first = ManualSignals::new();
+        let second = ManualSignals::new();
+        let merged = MergedSignals::new().with(first.clone()).with(second.clone());
+        Box::new(merged).start(sn);
+
+        second.raise(Signal::TERM);
+        first.raise(Signal::INT);
+        assert_eq!(rc.recv(), Some(Signal::TERM));
+        assert_eq!(rc.recv(), Some(Signal::INT));
+    }
+}
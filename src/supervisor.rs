@@ -0,0 +1,988 @@
+use traits::{Runner, Signal, Receiver, Sender};
+use events::{self, Events, EventKind, outcome_event};
+use composer::{MemberError, MembershipError, Reply, group_error, setup_member, unknown_member};
+use panic::catch_runner;
+use shutdown::StillRunning;
+use ready::Ready;
+use logging;
+use {MaridError};
+use chan;
+use std::thread;
+use std::fmt;
+use std::error::Error;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+type Exit = (usize, usize, Result<(), MaridError>);
+
+/// Determines when a child of a Supervisor is restarted.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum RestartType {
+    /// The child is always restarted.
+    Permanent,
+    /// The child is only restarted when it fails.
+    Transient,
+    /// The child is never restarted.
+    Temporary,
+}
+
+/// Determines which children a Supervisor restarts when one of them is restarted.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Strategy {
+    /// Only the exited child is restarted.
+    OneForOne,
+    /// Every other child is stopped, then all of them are restarted.
+    OneForAll,
+    /// The children declared after the exited child are stopped, then the exited child
+    /// and those children are restarted.
+    RestForOne,
+}
+
+/// The specification of a child of a Supervisor.
+pub struct ChildSpec<M = Signal> {
+    name: String,
+    restart: RestartType,
+    factory: Box<FnMut() -> Box<Runner<M> + Send> + Send>,
+}
+
+impl<M> ChildSpec<M> {
+    /// Creates a new ChildSpec, building the child's runners with the factory.
+    pub fn new<F>(name: &str, restart: RestartType, factory: F) -> ChildSpec<M>
+        where F: FnMut() -> Box<Runner<M> + Send> + Send + 'static {
+        ChildSpec {
+            name: name.to_string(),
+            restart: restart,
+            factory: Box::new(factory),
+        }
+    }
+}
+
+/// Error returned by a Supervisor that has restarted its children too often.
+#[derive(Debug)]
+pub struct RestartLimitError {
+    /// Name of the child whose restart exceeded the limit.
+    pub name: String,
+    /// The error the child exited or failed its setup with, if any.
+    pub error: Option<MaridError>,
+}
+
+impl fmt::Display for RestartLimitError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.error {
+            Some(ref e) => write!(fmt, "child {} restarted too often: {}", self.name, e),
+            None => write!(fmt, "child {} restarted too often", self.name),
+        }
+    }
+}
+
+impl Error for RestartLimitError {
+    fn description(&self) -> &str {
+        "child restarted too often"
+    }
+}
+
+struct Child<M> {
+    spec: ChildSpec<M>,
+    runner: Option<Box<Runner<M> + Send>>,
+    // Some while the child is running.
+    signals: Option<Sender<M>>,
+    generation: usize,
+    ready: bool,
+    // Stopped through a SupervisorHandle, and not restarted until asked to.
+    held: bool,
+}
+
+enum Command<M> {
+    Signal(String, M, Reply),
+    Stop(String, M, Reply),
+    Restart(String, M, Reply),
+}
+
+/// A handle used to signal, stop and restart the children of a running Supervisor.
+///
+/// Handles are created with `Supervisor::handle()` and can be cloned and sent to other
+/// threads. Commands given before the Supervisor has started running are handled once
+/// it does. Children are named by their ChildSpec.
+pub struct SupervisorHandle<M = Signal> {
+    commands: Sender<Command<M>>,
+    closed: Arc<Mutex<bool>>,
+}
+
+impl<M> Clone for SupervisorHandle<M> {
+    fn clone(&self) -> SupervisorHandle<M> {
+        SupervisorHandle {
+            commands: self.commands.clone(),
+            closed: self.closed.clone(),
+        }
+    }
+}
+
+impl<M> SupervisorHandle<M> {
+    /// Sends the signal to the named child only.
+    ///
+    /// Fails with `MembershipError::UnknownMember` if no running child has the given name.
+    pub fn signal(&self, name: &str, signal: M) -> Result<(), MaridError> {
+        self.request(|reply| Command::Signal(name.to_string(), signal, reply))
+    }
+
+    /// Sends the signal to the named child and waits for it to exit, as the Supervisor
+    /// does for the children it stops itself. The child is not restarted until asked to
+    /// with `restart()`. Blocks the Supervisor until the child has exited, or the
+    /// shutdown timeout has elapsed.
+    ///
+    /// Fails with `MembershipError::UnknownMember` if no running child has the given name.
+    pub fn stop(&self, name: &str, signal: M) -> Result<(), MaridError> {
+        self.request(|reply| Command::Stop(name.to_string(), signal, reply))
+    }
+
+    /// Stops the named child with the signal if it is running, then starts a new runner
+    /// built by its ChildSpec. Such restarts do not count towards the intensity.
+    ///
+    /// Returns the error of the new runner's setup, in which case the child is left
+    /// stopped. Fails with `MembershipError::UnknownMember` if no child has the given name.
+    pub fn restart(&self, name: &str, signal: M) -> Result<(), MaridError> {
+        self.request(|reply| Command::Restart(name.to_string(), signal, reply))
+    }
+
+    // Sends the command to the Supervisor, and waits for its reply.
+    fn request<F>(&self, command: F) -> Result<(), MaridError> where F: FnOnce(Reply) -> Command<M> {
+        let (sn, rc) = mpsc::channel();
+        {
+            let closed = self.closed.lock().unwrap_or_else(|e| e.into_inner());
+            if *closed {
+                return Err(Box::new(MembershipError::Closed))
+            }
+            self.commands.send(command(sn));
+        }
+        rc.recv().unwrap_or_else(|_| Err(Box::new(MembershipError::Closed)))
+    }
+}
+
+/// A Runner supervising a set of children, restarting them as they exit.
+///
+/// Children are setup in order and each run on its own thread. When a child exits it
+/// is restarted according to its RestartType and the Supervisor's Strategy. When more
+/// than the maximum number of restarts happen within the period, the Supervisor stops
+/// its remaining children and fails with a RestartLimitError. The default intensity is
+/// 3 restarts within 5 seconds.
+///
+/// A child failing its setup fails the Supervisor's own setup with a GroupError, as with
+/// the members of a Composer.
+///
+/// When the Supervisor receives its shutdown_signal, it is sent to the children in
+/// reverse order, waiting for each to exit, after which the Supervisor returns a
+/// GroupError of the children that failed, without a trigger. A child still running once the shutdown
+/// timeout has elapsed is given up on, and reported as `StillRunning`. Other signals are
+/// forwarded to every running child. The Supervisor also returns once none of its
+/// children are left running. Since a Supervisor is itself a Runner, supervisors can be
+/// nested by returning one from a child's factory.
+///
+/// Children are started with `Runner::run_ready()`. The Supervisor closes its own Ready
+/// handle once every running child has closed theirs.
+///
+/// The Supervisor emits the lifecycle events of its children, tagged with their index and
+/// name, including the setup events of every new runner.
+///
+/// Children can be signaled, stopped and restarted by name through a SupervisorHandle.
+/// The Supervisor keeps running while a child stopped that way may still be restarted.
+///
+/// # Blocking
+///
+/// The Supervisor stops its children one at a time, waiting up to the shutdown timeout
+/// for each. Meanwhile it does not handle signals, handle commands or other exits,
+/// which are handled once the children have been stopped. Stopping several children,
+/// as the OneForAll and RestForOne strategies do on a restart, may take up to the
+/// shutdown timeout for each of them.
+pub struct Supervisor<M = Signal> {
+    strategy: Strategy,
+    shutdown_signal: M,
+    children: Vec<Child<M>>,
+    max_restarts: usize,
+    period: Duration,
+    shutdown_timeout: Duration,
+    restarts: VecDeque<Instant>,
+    is_setup: bool,
+    exit_send: Sender<Exit>,
+    exit_recv: Receiver<Exit>,
+    pending: VecDeque<Exit>,
+    ready_send: Sender<(usize, usize)>,
+    ready_recv: Receiver<(usize, usize)>,
+    command_send: Sender<Command<M>>,
+    command_recv: Receiver<Command<M>>,
+    closed: Arc<Mutex<bool>>,
+    events: Events<M>,
+}
+
+impl<M> Supervisor<M> where M: Clone + PartialEq + fmt::Debug + Send + 'static {
+    /// Creates a new Supervisor.
+    ///
+    /// The shutdown_signal stops the Supervisor, and is sent to children that are stopped
+    /// by the Supervisor in order to be restarted, or because the restart limit was reached.
+    pub fn new(strategy: Strategy, shutdown_signal: M) -> Supervisor<M> {
+        let (exit_send, exit_recv) = chan::async();
+        let (ready_send, ready_recv) = chan::async();
+        let (command_send, command_recv) = chan::async();
+        Supervisor {
+            strategy: strategy,
+            shutdown_signal: shutdown_signal,
+            children: vec!(),
+            max_restarts: 3,
+            period: Duration::from_secs(5),
+            shutdown_timeout: Duration::from_secs(5),
+            restarts: VecDeque::new(),
+            is_setup: false,
+            exit_send: exit_send,
+            exit_recv: exit_recv,
+            pending: VecDeque::new(),
+            ready_send: ready_send,
+            ready_recv: ready_recv,
+            command_send: command_send,
+            command_recv: command_recv,
+            closed: Arc::new(Mutex::new(false)),
+            events: Events::new(),
+        }
+    }
+
+    /// Adds a child, after any children already added.
+    pub fn child(mut self, spec: ChildSpec<M>) -> Supervisor<M> {
+        self.children.push(Child {
+            spec: spec,
+            runner: None,
+            signals: None,
+            generation: 0,
+            ready: false,
+            held: false,
+        });
+        self
+    }
+
+    /// Sets the maximum number of restarts allowed within the period.
+    pub fn intensity(mut self, max_restarts: usize, period: Duration) -> Supervisor<M> {
+        self.max_restarts = max_restarts;
+        self.period = period;
+        self
+    }
+
+    /// Sets how long the Supervisor waits for each child it stops to exit, which defaults
+    /// to 5 seconds. The Supervisor handles nothing else while it waits.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Supervisor<M> {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Returns a SupervisorHandle used to signal, stop and restart children while the
+    /// Supervisor runs.
+    pub fn handle(&self) -> SupervisorHandle<M> {
+        SupervisorHandle {
+            commands: self.command_send.clone(),
+            closed: self.closed.clone(),
+        }
+    }
+
+    fn child_events(&self, idx: usize) -> Events<M> {
+        self.events.member(idx, Some(&self.children[idx].spec.name))
+    }
+
+    fn running(&self) -> usize {
+        self.children.iter().filter(|c| c.signals.is_some()).count()
+    }
+
+    fn held(&self) -> bool {
+        self.children.iter().any(|c| c.held)
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.children.iter().position(|c| c.spec.name == name)
+    }
+
+    fn find_running(&self, name: &str) -> Option<usize> {
+        self.find(name).and_then(|idx| self.children[idx].signals.as_ref().map(|_| idx))
+    }
+
+    fn command(&mut self, command: Command<M>) {
+        match command {
+            Command::Signal(name, signal, reply) => {
+                let res = self.find_running(&name).map(|idx| self.send(idx, signal));
+                let _ = reply.send(res.ok_or_else(|| unknown_member(name)));
+            },
+            Command::Stop(name, signal, reply) => {
+                let res = self.find_running(&name).map(|idx| {
+                    self.children[idx].held = true;
+                    let _ = self.stop(idx, signal);
+                    self.child_events(idx).emit(EventKind::Removed);
+                });
+                let _ = reply.send(res.ok_or_else(|| unknown_member(name)));
+            },
+            Command::Restart(name, signal, reply) => {
+                let idx = match self.find(&name) {
+                    Some(idx) => idx,
+                    None => {
+                        let _ = reply.send(Err(unknown_member(name)));
+                        return
+                    },
+                };
+                if self.children[idx].signals.is_some() {
+                    let _ = self.stop(idx, signal);
+                }
+                self.children[idx].held = true;
+                let res = self.incarnate(idx);
+                if res.is_ok() {
+                    self.children[idx].held = false;
+                    self.child_events(idx).emit(EventKind::Restarted);
+                    self.start(idx);
+                }
+                let _ = reply.send(res);
+            },
+        }
+    }
+
+    fn all_ready(&self) -> bool {
+        self.children.iter().all(|c| c.ready || c.signals.is_none())
+    }
+
+    // Marks the child as ready, unless the report comes from an earlier incarnation.
+    fn mark_ready(&mut self, idx: usize, generation: usize) {
+        let child = &mut self.children[idx];
+        if child.generation == generation && child.signals.is_some() {
+            child.ready = true;
+        }
+    }
+
+    // Builds and sets up a new runner for the child, emitting its setup events.
+    fn incarnate(&mut self, idx: usize) -> Result<(), MaridError> {
+        let mut runner = (self.children[idx].spec.factory)();
+        let events = self.child_events(idx);
+        runner.observe(events.clone());
+        try!(setup_member(&mut runner, &events));
+        self.children[idx].runner = Some(runner);
+        Ok(())
+    }
+
+    fn start(&mut self, idx: usize) {
+        let runner = self.children[idx].runner.take().expect("Child was not set up");
+        self.child_events(idx).emit(EventKind::Running);
+        let (sn, rc) = chan::sync(1024);
+        let exits = self.exit_send.clone();
+        let readies = self.ready_send.clone();
+        let ready_events = self.child_events(idx);
+        let child = &mut self.children[idx];
+        child.generation += 1;
+        child.signals = Some(sn);
+        child.ready = false;
+        let generation = child.generation;
+        let ready = Ready::new(move || {
+            ready_events.emit(EventKind::Ready);
+            readies.send((idx, generation))
+        });
+        thread::spawn(move || {
+            let res = catch_runner(move || runner.run_ready(rc, ready));
+            exits.send((idx, generation, res));
+        });
+    }
+
+    // Marks the child as exited, returning false for an exit of an earlier incarnation.
+    fn exited(&mut self, idx: usize, generation: usize) -> bool {
+        let child = &mut self.children[idx];
+        if child.generation != generation || child.signals.is_none() {
+            return false
+        }
+        child.signals = None;
+        true
+    }
+
+    fn send(&self, idx: usize, signal: M) {
+        if let Some(ref signals) = self.children[idx].signals {
+            self.child_events(idx).emit(EventKind::SignalDelivered(signal.clone()));
+            signals.send(signal);
+        }
+    }
+
+    // Signals the child and waits for it to exit, returning its result. Other exits are
+    // kept for later. A child still running after the shutdown timeout is given up on.
+    fn stop(&mut self, idx: usize, signal: M) -> Result<(), MaridError> {
+        self.send(idx, signal);
+        let generation = self.children[idx].generation;
+        let mut exit = self.pending.iter()
+            .position(|e| e.0 == idx && e.1 == generation)
+            .and_then(|pos| self.pending.remove(pos));
+
+        let timer = chan::after(self.shutdown_timeout);
+        let exits = self.exit_recv.clone();
+        while exit.is_none() {
+            let next;
+            chan_select! {
+                exits.recv() -> e => next = e.expect("Exit channel closed"),
+                timer.recv() => {
+                    self.children[idx].signals = None;
+                    logging::abandon(&events::label(&self.child_events(idx)));
+                    return Err(Box::new(StillRunning))
+                },
+            }
+            if next.0 == idx && next.1 == generation {
+                exit = Some(next);
+            } else {
+                self.pending.push_back(next);
+            }
+        }
+
+        let (_, _, res) = exit.expect("Exit missing");
+        self.children[idx].signals = None;
+        self.child_events(idx).emit(outcome_event(&res, EventKind::Exited));
+        res
+    }
+
+    // Stops the running children among the given ones in reverse order, returning
+    // the ones that were running along with their results.
+    fn stop_all<I>(&mut self, idxs: I, signal: M) -> Vec<(usize, Result<(), MaridError>)>
+        where I: DoubleEndedIterator<Item=usize> {
+        let mut stopped = vec!();
+        for idx in idxs.rev() {
+            if self.children[idx].signals.is_some() {
+                let res = self.stop(idx, signal.clone());
+                stopped.insert(0, (idx, res));
+            }
+        }
+        stopped
+    }
+
+    // Stops every child, returning the failures of those that did not exit cleanly.
+    fn shutdown(&mut self) -> Result<(), MaridError> {
+        let signal = self.shutdown_signal.clone();
+        let count = self.children.len();
+        // Failures are listed in the order the children were stopped. Each of them is a
+        // consequence of the shutdown, none triggered it.
+        let failures: Vec<MemberError> = self.stop_all(0..count, signal).into_iter().rev()
+            .filter_map(|(idx, res)| res.err().map(|e| (idx, e)))
+            .map(|(idx, e)| MemberError {
+                index: idx,
+                name: Some(self.children[idx].spec.name.clone()),
+                error: e,
+            })
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(group_error(failures, false)))
+        }
+    }
+
+    // Records a restart, returning false if the intensity has been exceeded.
+    fn may_restart(&mut self) -> bool {
+        let now = Instant::now();
+        while self.restarts.front().map_or(false, |t| now.duration_since(*t) > self.period) {
+            self.restarts.pop_front();
+        }
+        if self.restarts.len() >= self.max_restarts {
+            return false
+        }
+        self.restarts.push_back(now);
+        true
+    }
+
+    fn give_up(&mut self, idx: usize, error: Option<MaridError>) -> MaridError {
+        let signal = self.shutdown_signal.clone();
+        let count = self.children.len();
+        self.stop_all(0..count, signal);
+        Box::new(RestartLimitError {
+            name: self.children[idx].spec.name.clone(),
+            error: error,
+        })
+    }
+
+    fn handle_exit(&mut self, exit: Exit) -> Result<(), MaridError> {
+        let (idx, generation, res) = exit;
+        if !self.exited(idx, generation) {
+            return Ok(())
+        }
+        self.child_events(idx).emit(outcome_event(&res, EventKind::Exited));
+
+        let restart = match self.children[idx].spec.restart {
+            RestartType::Permanent => true,
+            RestartType::Transient => res.is_err(),
+            RestartType::Temporary => false,
+        };
+        if !restart {
+            return Ok(())
+        }
+
+        let signal = self.shutdown_signal.clone();
+        let count = self.children.len();
+        let stopped = match self.strategy {
+            Strategy::OneForOne => vec!(),
+            Strategy::OneForAll => self.stop_all(0..count, signal),
+            Strategy::RestForOne => self.stop_all(idx + 1..count, signal),
+        };
+        let mut idxs: Vec<usize> = stopped.into_iter().map(|(i, _)| i).collect();
+        idxs.retain(|&i| self.children[i].spec.restart != RestartType::Temporary);
+        idxs.push(idx);
+        idxs.sort();
+
+        if !self.may_restart() {
+            return Err(self.give_up(idx, res.err()))
+        }
+        for i in idxs {
+            while let Err(e) = self.incarnate(i) {
+                if !self.may_restart() {
+                    return Err(self.give_up(i, Some(e)))
+                }
+            }
+            self.child_events(i).emit(EventKind::Restarted);
+            self.start(i);
+        }
+        Ok(())
+    }
+}
+
+impl<M> Runner<M> for Supervisor<M> where M: Clone + PartialEq + fmt::Debug + Send + 'static {
+    fn run(self: Box<Self>, signals: Receiver<M>) -> Result<(), MaridError> {
+        self.run_ready(signals, Ready::unobserved())
+    }
+
+    fn run_ready(mut self: Box<Self>, signals: Receiver<M>, ready: Ready) -> Result<(), MaridError> {
+        if !self.is_setup {
+            try!(self.setup());
+        }
+        for idx in 0..self.children.len() {
+            self.start(idx);
+        }
+
+        let mut signals = signals;
+        // Swapped in for the signal channel once it has been closed.
+        let (_closed_sn, closed_rc) = chan::sync(0);
+        let exits = self.exit_recv.clone();
+        let readies = self.ready_recv.clone();
+        let commands = self.command_recv.clone();
+        let mut ready = Some(ready);
+        while self.running() > 0 || self.held() {
+            if let Some(exit) = self.pending.pop_front() {
+                try!(self.handle_exit(exit));
+                continue
+            }
+            if self.all_ready() {
+                if let Some(ready) = ready.take() {
+                    ready.close();
+                }
+            }
+
+            let mut exit = None;
+            let mut signal = None;
+            let mut command = None;
+            chan_select! {
+                signals.recv() -> sig => signal = Some(sig),
+                exits.recv() -> res => exit = Some(res.expect("Exit channel closed")),
+                readies.recv() -> r => {
+                    let (idx, generation) = r.expect("Ready channel closed");
+                    self.mark_ready(idx, generation);
+                },
+                commands.recv() -> cmd => command = Some(cmd.expect("Command channel closed")),
+            }
+            if let Some(command) = command {
+                self.command(command);
+            }
+
+            match signal {
+                Some(Some(ref sig)) if *sig == self.shutdown_signal => return self.shutdown(),
+                Some(Some(sig)) => {
+                    for idx in 0..self.children.len() {
+                        self.send(idx, sig.clone());
+                    }
+                },
+                Some(None) => signals = closed_rc.clone(),
+                None => {},
+            }
+            if let Some(exit) = exit {
+                try!(self.handle_exit(exit));
+            }
+        }
+        Ok(())
+    }
+
+    fn observe(&mut self, events: Events<M>) {
+        self.events = events;
+    }
+
+    fn setup(&mut self) -> Result<(), MaridError> {
+        for idx in 0..self.children.len() {
+            if let Err(e) = self.incarnate(idx) {
+                let failure = MemberError {
+                    index: idx,
+                    name: Some(self.children[idx].spec.name.clone()),
+                    error: e,
+                };
+                return Err(Box::new(group_error(vec!(failure), true)))
+            }
+        }
+        self.is_setup = true;
+        Ok(())
+    }
+}
+
+impl<M> Drop for Supervisor<M> {
+    // Refuses any command still queued or sent from now on.
+    fn drop(&mut self) {
+        *self.closed.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        let commands = self.command_recv.clone();
+        let mut empty = false;
+        while !empty {
+            chan_select! {
+                default => empty = true,
+                commands.recv() -> cmd => empty = cmd.is_none(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Supervisor, ChildSpec, RestartType, Strategy, RestartLimitError};
+    use test_helpers::TestError;
+    use {Runner, FnRunner, Signal, Receiver, MaridError, Events, EventKind, GroupError, Ready, MembershipError};
+    use chan;
+    use std::thread;
+    use std::sync::{Arc, Mutex, mpsc};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    // Builds runners that fail straight away for their first `failures` incarnations,
+    // and otherwise run until signaled. Each started runner is reported on `started`.
+    fn worker(name: &'static str, failures: usize, count: Arc<AtomicUsize>,
+              started: mpsc::Sender<&'static str>)
+        -> Box<FnMut() -> Box<Runner + Send> + Send> {
+        Box::new(move || {
+            let n = count.fetch_add(1, Ordering::SeqCst);
+            let started = started.clone();
+            Box::new(FnRunner::new(move |signals: Receiver<Signal>| {
+                started.send(name).unwrap();
+                if n < failures {
+                    return Err(Box::new(TestError) as MaridError)
+                }
+                signals.recv();
+                Ok(())
+            })) as Box<Runner + Send>
+        })
+    }
+
+    struct Harness {
+        counts: Vec<Arc<AtomicUsize>>,
+        started: mpsc::Receiver<&'static str>,
+        signals: chan::Sender<Signal>,
+        handle: thread::JoinHandle<Result<(), MaridError>>,
+    }
+
+    // Runs a supervisor with a child for every given number of failures.
+    fn supervise(strategy: Strategy, failures: Vec<usize>) -> Harness {
+        let names = ["a", "b", "c"];
+        let (started_sn, started_rc) = mpsc::channel();
+        let mut counts = vec!();
+        let mut supervisor = Supervisor::new(strategy, Signal::INT);
+        for (i, f) in failures.into_iter().enumerate() {
+            let count = Arc::new(AtomicUsize::new(0));
+            let mut factory = worker(names[i], f, count.clone(), started_sn.clone());
+            supervisor = supervisor.child(ChildSpec::new(names[i], RestartType::Permanent,
+                                                         move || factory()));
+            counts.push(count);
+        }
+
+        let (sn, rc) = chan::sync(1);
+        let handle = thread::spawn(move || Box::new(supervisor).run(rc));
+        Harness {
+            counts: counts,
+            started: started_rc,
+            signals: sn,
+            handle: handle,
+        }
+    }
+
+    impl Harness {
+        fn wait_started(&self, n: usize) {
+            for _ in 0..n {
+                self.started.recv_timeout(Duration::from_secs(5)).expect("Child did not start");
+            }
+        }
+
+        fn stop(self) -> Vec<usize> {
+            self.signals.send(Signal::INT);
+            assert!(self.handle.join().unwrap().is_ok());
+            self.counts.iter().map(|c| c.load(Ordering::SeqCst)).collect()
+        }
+    }
+
+    #[test]
+    fn test_one_for_one() {
+        let harness = supervise(Strategy::OneForOne, vec!(1, 0, 0));
+        harness.wait_started(4);
+        assert_eq!(harness.stop(), vec!(2, 1, 1));
+    }
+
+    #[test]
+    fn test_one_for_all() {
+        let harness = supervise(Strategy::OneForAll, vec!(1, 0, 0));
+        harness.wait_started(6);
+        assert_eq!(harness.stop(), vec!(2, 2, 2));
+    }
+
+    #[test]
+    fn test_rest_for_one() {
+        let harness = supervise(Strategy::RestForOne, vec!(0, 1, 0));
+        harness.wait_started(5);
+        assert_eq!(harness.stop(), vec!(1, 2, 2));
+    }
+
+    #[test]
+    fn test_forward_signals() {
+        let harness = supervise(Strategy::OneForOne, vec!(0, 0, 0));
+        harness.wait_started(3);
+        // Each child exits on HUP and is restarted, while the supervisor keeps running.
+        harness.signals.send(Signal::HUP);
+        harness.wait_started(3);
+        assert_eq!(harness.stop(), vec!(2, 2, 2));
+    }
+
+    #[test]
+    fn test_shutdown_errors() {
+        let (_release_sn, release_rc) = mpsc::channel::<()>();
+        let release = Arc::new(Mutex::new(release_rc));
+        let supervisor = Box::new(Supervisor::new(Strategy::OneForOne, Signal::INT)
+                                  .shutdown_timeout(Duration::from_millis(20))
+                                  .child(ChildSpec::new("failing", RestartType::Permanent, || {
+                                      Box::new(FnRunner::new(|signals: Receiver<Signal>| {
+                                          signals.recv();
+                                          Err(Box::new(TestError) as MaridError)
+                                      })) as Box<Runner + Send>
+                                  }))
+                                  // Never exits while the test is running.
+                                  .child(ChildSpec::new("stuck", RestartType::Permanent, move || {
+                                      let release = release.clone();
+                                      Box::new(FnRunner::new(move |_signals| {
+                                          let _ = release.lock().unwrap().recv();
+                                          Ok(())
+                                      })) as Box<Runner + Send>
+                                  })));
+
+        let (sn, rc) = chan::sync(1);
+        sn.send(Signal::INT);
+        let err = supervisor.run(rc).err().expect("Expected an error");
+        let err = err.downcast_ref::<GroupError>().expect("Expected a GroupError");
+        let names: Vec<&str> = err.failures().iter().map(|f| f.name.as_ref().unwrap().as_str()).collect();
+        assert_eq!(names, vec!("stuck", "failing"));
+        assert_eq!(err.still_running().len(), 1);
+        assert!(err.trigger().is_none());
+    }
+
+    // Fails its setup.
+    struct BadSetup;
+
+    impl Runner for BadSetup {
+        fn setup(&mut self) -> Result<(), MaridError> {
+            Err(Box::new(TestError))
+        }
+
+        fn run(self: Box<Self>, _signals: Receiver<Signal>) -> Result<(), MaridError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_setup_error() {
+        let (started_sn, _started_rc) = mpsc::channel();
+        let mut factory = worker("a", 0, Arc::new(AtomicUsize::new(0)), started_sn);
+        let mut supervisor = Box::new(Supervisor::new(Strategy::OneForOne, Signal::INT)
+                                      .child(ChildSpec::new("a", RestartType::Permanent,
+                                                            move || factory()))
+                                      .child(ChildSpec::new("bad", RestartType::Permanent,
+                                                            || Box::new(BadSetup) as Box<Runner + Send>)));
+
+        let err = supervisor.setup().err().expect("Expected a setup error");
+        let err = err.downcast_ref::<GroupError>().expect("Expected a GroupError");
+        let trigger = err.trigger().expect("Expected a trigger");
+        assert_eq!((trigger.index, trigger.name.as_ref().map(|n| &n[..])), (1, Some("bad")));
+        assert!(trigger.error.is::<TestError>());
+    }
+
+    // Closes its Ready handle once released, then waits for a signal.
+    struct LateReady {
+        release: mpsc::Receiver<()>,
+    }
+
+    impl Runner for LateReady {
+        fn setup(&mut self) -> Result<(), MaridError> {
+            Ok(())
+        }
+
+        fn run(self: Box<Self>, signals: Receiver<Signal>) -> Result<(), MaridError> {
+            self.run_ready(signals, Ready::unobserved())
+        }
+
+        fn run_ready(self: Box<Self>, signals: Receiver<Signal>, ready: Ready) -> Result<(), MaridError> {
+            let _ = self.release.recv();
+            ready.close();
+            signals.recv();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_supervisor_ready() {
+        let (release_sn, release_rc) = mpsc::channel();
+        let release = Arc::new(Mutex::new(Some(release_rc)));
+        let (started_sn, _started_rc) = mpsc::channel();
+        let mut factory = worker("a", 0, Arc::new(AtomicUsize::new(0)), started_sn);
+        let supervisor = Box::new(Supervisor::new(Strategy::OneForOne, Signal::INT)
+                                  .child(ChildSpec::new("a", RestartType::Permanent,
+                                                        move || factory()))
+                                  .child(ChildSpec::new("late", RestartType::Permanent, move || {
+                                      let release = release.lock().unwrap().take().unwrap();
+                                      Box::new(LateReady { release: release }) as Box<Runner + Send>
+                                  })));
+
+        let (ready_sn, ready_rc) = mpsc::channel();
+        let ready = Ready::new(move || ready_sn.send(()).unwrap());
+        let (sn, rc) = chan::sync(1);
+        let handle = thread::spawn(move || supervisor.run_ready(rc, ready));
+
+        // The Supervisor is not ready until its second child is.
+        assert!(ready_rc.recv_timeout(Duration::from_millis(20)).is_err());
+        release_sn.send(()).unwrap();
+        ready_rc.recv_timeout(Duration::from_secs(5)).expect("Supervisor did not become ready");
+        sn.send(Signal::INT);
+        assert!(handle.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_supervisor_handle() {
+        let (started_sn, started_rc) = mpsc::channel();
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut factory = worker("a", 0, count.clone(), started_sn);
+        let supervisor = Box::new(Supervisor::new(Strategy::OneForOne, Signal::INT)
+                                  .child(ChildSpec::new("a", RestartType::Permanent,
+                                                        move || factory())));
+        let handle = supervisor.handle();
+        let unknown = |res: Result<(), MaridError>| {
+            res.err().and_then(|e| e.downcast_ref::<MembershipError>().cloned())
+        };
+
+        let (sn, rc) = chan::sync(1);
+        let thread = thread::spawn(move || supervisor.run(rc));
+        started_rc.recv_timeout(Duration::from_secs(5)).expect("Child did not start");
+
+        // The stopped child is not restarted, while the Supervisor keeps running.
+        assert!(handle.stop("a", Signal::INT).is_ok());
+        assert_eq!(unknown(handle.signal("a", Signal::HUP)),
+                   Some(MembershipError::UnknownMember("a".to_string())));
+        assert!(handle.restart("a", Signal::INT).is_ok());
+        started_rc.recv_timeout(Duration::from_secs(5)).expect("Child did not restart");
+        // A running child is stopped before it is restarted.
+        assert!(handle.restart("a", Signal::INT).is_ok());
+        started_rc.recv_timeout(Duration::from_secs(5)).expect("Child did not restart");
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(unknown(handle.restart("nobody", Signal::INT)),
+                   Some(MembershipError::UnknownMember("nobody".to_string())));
+
+        sn.send(Signal::INT);
+        assert!(thread.join().unwrap().is_ok());
+        assert_eq!(unknown(handle.signal("a", Signal::HUP)), Some(MembershipError::Closed));
+    }
+
+    #[test]
+    fn test_restart_intensity() {
+        let (started_sn, _started_rc) = mpsc::channel();
+        let mut factory = worker("a", 100, Arc::new(AtomicUsize::new(0)), started_sn);
+        let supervisor = Box::new(Supervisor::new(Strategy::OneForOne, Signal::INT)
+                                  .intensity(2, Duration::from_secs(60))
+                                  .child(ChildSpec::new("a", RestartType::Permanent,
+                                                        move || factory())));
+
+        let (_sn, rc) = chan::sync(1);
+        let err = supervisor.run(rc).err().expect("Expected an error");
+        let err = err.downcast_ref::<RestartLimitError>().expect("Expected a RestartLimitError");
+        assert_eq!(err.name, "a");
+        assert!(err.error.is_some());
+    }
+
+    #[test]
+    fn test_restart_types() {
+        let (started_sn, _started_rc) = mpsc::channel();
+        let transient = Arc::new(AtomicUsize::new(0));
+        let temporary = Arc::new(AtomicUsize::new(0));
+        let transient_clone = transient.clone();
+        let temporary_clone = temporary.clone();
+        let mut failing = worker("temporary", 1, temporary_clone, started_sn);
+        let supervisor = Box::new(Supervisor::new(Strategy::OneForOne, Signal::INT)
+                                  .child(ChildSpec::new("transient", RestartType::Transient, move || {
+                                      transient_clone.fetch_add(1, Ordering::SeqCst);
+                                      Box::new(FnRunner::new(|_sigs| Ok(()))) as Box<Runner + Send>
+                                  }))
+                                  .child(ChildSpec::new("temporary", RestartType::Temporary,
+                                                        move || failing())));
+
+        // Neither child is restarted, so the supervisor returns on its own.
+        let (_sn, rc) = chan::sync(1);
+        assert!(supervisor.run(rc).is_ok());
+        assert_eq!(transient.load(Ordering::SeqCst), 1);
+        assert_eq!(temporary.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_nested_supervisors() {
+        let (started_sn, started_rc) = mpsc::channel();
+        let inner_count = Arc::new(AtomicUsize::new(0));
+        let inner_clone = inner_count.clone();
+        let supervisor = Box::new(Supervisor::new(Strategy::OneForOne, Signal::INT)
+                                  .child(ChildSpec::new("inner", RestartType::Permanent, move || {
+                                      let mut factory = worker("leaf", 1, inner_clone.clone(),
+                                                               started_sn.clone());
+                                      Box::new(Supervisor::new(Strategy::OneForOne, Signal::INT)
+                                               .child(ChildSpec::new("leaf", RestartType::Permanent,
+                                                                     move || factory())))
+                                          as Box<Runner + Send>
+                                  })));
+
+        let (sn, rc) = chan::sync(1);
+        let handle = thread::spawn(move || supervisor.run(rc));
+        // The leaf is restarted by the inner supervisor.
+        for _ in 0..2 {
+            started_rc.recv_timeout(Duration::from_secs(5)).expect("Leaf did not start");
+        }
+        sn.send(Signal::INT);
+        assert!(handle.join().unwrap().is_ok());
+        assert_eq!(inner_count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_supervisor_events() {
+        let (started_sn, started_rc) = mpsc::channel();
+        let mut factory = worker("a", 1, Arc::new(AtomicUsize::new(0)), started_sn);
+        let mut supervisor = Box::new(Supervisor::new(Strategy::OneForOne, Signal::INT)
+                                      .child(ChildSpec::new("a", RestartType::Permanent,
+                                                            move || factory())));
+        let events = Events::new();
+        let rc = events.subscribe();
+        supervisor.observe(events);
+
+        let (sn, signals) = chan::sync(1);
+        let handle = thread::spawn(move || supervisor.run(signals));
+        for _ in 0..2 {
+            started_rc.recv_timeout(Duration::from_secs(5)).expect("Child did not start");
+        }
+        sn.send(Signal::INT);
+        assert!(handle.join().unwrap().is_ok());
+
+        let kinds: Vec<EventKind> = rc.try_iter().map(|e| {
+            assert_eq!((e.index, e.name), (Some(0), Some("a".to_string())));
+            e.kind
+        }).collect();
+        assert_eq!(kinds, vec!(EventKind::SetupStarted,
+                               EventKind::SetupFinished(Ok(())),
+                               EventKind::Running,
+                               EventKind::Ready,
+                               EventKind::Exited(Err("a testing error".to_string())),
+                               EventKind::SetupStarted,
+                               EventKind::SetupFinished(Ok(())),
+                               EventKind::Restarted,
+                               EventKind::Running,
+                               EventKind::Ready,
+                               EventKind::SignalDelivered(Signal::INT),
+                               EventKind::Exited(Ok(()))));
+    }
+}
@@ -1,31 +1,57 @@
 pub use chan_signal::Signal;
 pub use chan::{Sender, Receiver};
+use ready::Ready;
+use events::Events;
 use {MaridError};
 
 /// A type implementing the Runner trait has the job of performing some arbitrary
 /// work while waiting for a signal indication shutdown. Upon receiving that
 /// defined shutdown Signal, the Runner must exit in a finite period of time.
-pub trait Runner {
+///
+/// Runners receive messages of type `M`, which defaults to an OS `Signal`. Applications
+/// that need to send their own commands, such as a reload or a drain, use their own
+/// message type, typically an enum with a variant wrapping `Signal`.
+pub trait Runner<M = Signal> {
     /// Performs work for an indefinite amount of time.
     ///
     /// The Box<Self> form is used here in order to allow Process types the ability to run
     /// different types of Runners at once.
-    fn run(self: Box<Self>, signals: Receiver<Signal>) -> Result<(), MaridError>;
+    fn run(self: Box<Self>, signals: Receiver<M>) -> Result<(), MaridError>;
 
     /// Used to do any setup work necessary for the Runner.
     ///
     /// This function should only complete once the type is ready to be run,
     /// and must complete in a finite period of time.
     fn setup(&mut self) -> Result<(), MaridError>;
+
+    /// Performs work like run(), closing the Ready handle once the Runner is ready.
+    ///
+    /// Runners that only become ready partway through their work, for example once
+    /// they have bound a socket, override this method and implement run() by calling
+    /// it with `Ready::unobserved()`. The default implementation closes the handle
+    /// right away and calls run().
+    fn run_ready(self: Box<Self>, signals: Receiver<M>, ready: Ready) -> Result<(), MaridError> {
+        ready.close();
+        self.run(signals)
+    }
+
+    /// Gives the Runner the Events to which it emits the lifecycle events of its members.
+    ///
+    /// Called before setup(). The runners of this crate made up of other runners, such
+    /// as a Composer, pass it on to them. Other runners may only subscribe to the events
+    /// or read the metrics. The default implementation ignores it.
+    fn observe(&mut self, _events: Events<M>) {}
 }
 
 /// A Process represents are running unit of work. It can be signaled and waited on.
-pub trait Process {
+///
+/// As with a Runner, a Process is signaled with messages of type `M`.
+pub trait Process<M = Signal> {
     /// Error type for the Process.
     type Error;
 
     /// This function will block until the running Process has finished its setup and
-    /// is ready to run.
+    /// reported that it is ready.
     fn ready(&self) -> Result<(), Self::Error>;
     /// This function will wait until the Process has exited, returning a success or
     /// failure.
@@ -34,7 +60,7 @@ pub trait Process {
     ///
     /// ### Warnings
     /// This must be a non-blocking function.
-    fn signal(&self, signal: Signal);
+    fn signal(&self, signal: M);
 }
 
 #[cfg(test)]
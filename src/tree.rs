@@ -0,0 +1,218 @@
+use events::{self, Event, EventKind};
+use process::Status;
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// How often a SharedTree's follower checks whether its server has stopped.
+const FOLLOW_POLL_MS: u64 = 100;
+
+// A process followed alongside the tree, under a name.
+pub type Watched = (String, Box<Fn() -> Status + Send>);
+
+// A runner of the tree, keyed by its path from the root.
+struct Entry {
+    path: String,
+    index: Option<usize>,
+    name: Option<String>,
+    state: &'static str,
+}
+
+// The state of the runners of a process tree, as told by their events, keyed by their
+// path from the root so that the members of nested groups are kept apart.
+pub struct Tree {
+    runners: Vec<Entry>,
+    watched: Vec<Watched>,
+}
+
+impl Tree {
+    fn apply<M>(&mut self, event: Event<M>) {
+        let path = events::path(&event);
+        let state = match event.kind {
+            EventKind::SetupStarted => "setup",
+            EventKind::SetupFinished(Ok(())) | EventKind::Restarted => return,
+            EventKind::SetupFinished(Err(_)) => "failed",
+            EventKind::Running => "running",
+            EventKind::Ready => "ready",
+            EventKind::SignalDelivered(_) => return,
+            EventKind::Exited(Ok(())) => "exited",
+            EventKind::Exited(Err(_)) => "failed",
+            EventKind::Panicked(_) => "panicked",
+            EventKind::Removed => {
+                // Forgets the member, along with its own members.
+                let nested = format!("{}/", path);
+                self.runners.retain(|r| r.path != path && !r.path.starts_with(&nested));
+                return
+            },
+        };
+        match self.runners.iter().position(|r| r.path == path) {
+            Some(pos) => self.runners[pos].state = state,
+            None => self.runners.push(Entry {
+                path: path,
+                index: event.index,
+                name: event.name,
+                state: state,
+            }),
+        }
+    }
+
+    // Runners that exited successfully, or were removed from their group, no longer hold
+    // the tree back.
+    pub fn ready(&self) -> bool {
+        let ready = |state| state == "ready" || state == "exited";
+        self.runners.iter().all(|r| ready(r.state)) &&
+            self.watched.iter().all(|w| ready(state((w.1)())))
+    }
+
+    // One line per runner and watched process, giving its path or name and its state.
+    pub fn lines(&self) -> Vec<String> {
+        let runners = self.runners.iter().map(|r| format!("{} {}", r.path, r.state));
+        let watched = self.watched.iter().map(|w| format!("{} {}", w.0, state((w.1)())));
+        runners.chain(watched).collect()
+    }
+
+    // The readiness of the tree, and the state of each runner under its path.
+    pub fn json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "{{\"ready\":{},\"runners\":[", self.ready());
+        for (i, r) in self.runners.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let index = r.index.map(|i| i.to_string()).unwrap_or_else(|| "null".to_string());
+            let name = r.name.as_ref().map(|n| quote(n)).unwrap_or_else(|| "null".to_string());
+            let _ = write!(out, "{{\"path\":{},\"index\":{},\"name\":{},\"status\":\"{}\"}}",
+                           quote(&r.path), index, name, r.state);
+        }
+        out.push_str("],\"processes\":[");
+        for (i, &(ref name, ref status)) in self.watched.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{{\"name\":{},\"status\":\"{}\"}}", quote(name), state(status()));
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+// A Tree shared by the connections of a server, kept up to date by a follower thread.
+pub struct SharedTree {
+    tree: Mutex<Tree>,
+}
+
+impl SharedTree {
+    pub fn new(watched: Vec<Watched>) -> SharedTree {
+        SharedTree {
+            tree: Mutex::new(Tree {
+                runners: vec!(),
+                watched: watched,
+            }),
+        }
+    }
+
+    pub fn lock<'a>(&'a self) -> MutexGuard<'a, Tree> {
+        self.tree.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Applies the events to the tree as they are received, so that they do not pile up
+    // between requests, until the server has stopped or the events are no longer emitted.
+    pub fn follow<M>(&self, events: mpsc::Receiver<Event<M>>, stopped: &AtomicBool) {
+        while !stopped.load(Ordering::SeqCst) {
+            match events.recv_timeout(Duration::from_millis(FOLLOW_POLL_MS)) {
+                Ok(event) => self.lock().apply(event),
+                Err(mpsc::RecvTimeoutError::Timeout) => {},
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+// Names the state of a watched process like that of a runner of the tree.
+fn state(status: Status) -> &'static str {
+    match status {
+        Status::Initializing => "setup",
+        Status::Running => "running",
+        Status::Ready => "ready",
+        Status::Exited(Ok(())) => "exited",
+        Status::Exited(Err(_)) => "failed",
+        Status::Panicked => "panicked",
+    }
+}
+
+// Quotes a string as a JSON string.
+pub fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SharedTree, quote};
+    use events::{Events, EventKind};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use std::thread;
+    use Signal;
+
+    #[test]
+    fn test_tree_follow() {
+        let events = Events::<Signal>::new();
+        let tree = Arc::new(SharedTree::new(vec!()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let rc = events.subscribe();
+        let (follower_tree, follower_stopped) = (tree.clone(), stopped.clone());
+        let follower = thread::spawn(move || follower_tree.follow(rc, &follower_stopped));
+
+        let inner = events.member(0, Some("inner"));
+        inner.member(0, Some("web")).emit(EventKind::Running);
+        events.member(1, Some("web")).emit(EventKind::Exited(Ok(())));
+        // Members sharing a name in different groups are kept apart by their path.
+        let expected = vec!("inner/web running".to_string(), "web exited".to_string());
+        while tree.lock().lines() != expected {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        stopped.store(true, Ordering::SeqCst);
+        follower.join().unwrap();
+    }
+
+    #[test]
+    fn test_tree_removed() {
+        let events = Events::<Signal>::new();
+        let tree = SharedTree::new(vec!());
+        let rc = events.subscribe();
+        let inner = events.member(0, Some("inner"));
+        inner.emit(EventKind::Exited(Ok(())));
+        inner.member(0, None).emit(EventKind::Exited(Err("boom".to_string())));
+        events.member(1, Some("web")).emit(EventKind::Running);
+        for event in rc.try_iter() {
+            tree.lock().apply(event);
+        }
+        assert!(!tree.lock().ready());
+
+        // The removed member's own members are forgotten along with it.
+        inner.emit(EventKind::Removed);
+        tree.lock().apply(rc.recv().unwrap());
+        assert_eq!(tree.lock().lines(), vec!("web running".to_string()));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("a \"b\"\n\u{1}"), "\"a \\\"b\\\"\\n\\u0001\"");
+    }
+}
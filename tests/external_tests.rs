@@ -4,7 +4,8 @@ extern crate marid;
 extern crate chan;
 
 use marid::test_helpers::{TestRunner};
-use marid::{launch, Runner, MaridError, Composer, Process, Receiver, Signal, ProcessError};
+use marid::{launch, Runner, MaridError, Composer, GroupError, MembershipError, Process, Receiver, Signal, ProcessError};
+use marid::{ManualSignals, MergedSignals};
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 struct NullRunner;
@@ -58,7 +59,10 @@ fn test_launch_error() {
 
     match process.wait() {
         Ok(_) => assert!(false, "Expected an error"),
-        Err(ProcessError::RunnerError(_)) => {},
+        Err(ProcessError::RunnerError(e)) => {
+            let err = e.downcast_ref::<GroupError>().expect("Expected a GroupError");
+            assert_eq!(err.failures().len(), 2);
+        },
         Err(_) => assert!(false, "Wrong error type"),
     }
 }
@@ -86,3 +90,115 @@ fn test_launch_different_runners() {
     }
 }
 
+
+#[test]
+fn test_launch_signal_member() {
+    let (sn1, rc1) = chan::sync(0);
+    let runner1 = Box::new(TestRunner::new(1, sn1.clone())) as Box<Runner + Send>;
+    let (sn2, rc2) = chan::sync(0);
+    let runner2 = Box::new(TestRunner::new(2, sn2.clone())) as Box<Runner + Send>;
+
+    let mut composer = Composer::new(vec!(), Signal::INT)
+        .member("first", runner1)
+        .member("second", runner2);
+    let handle = composer.handle();
+    let signals = vec!(Signal::INT, Signal::HUP);
+
+    let process = launch(composer, signals);
+
+    assert!(process.ready().is_ok());
+
+    let err = handle.signal("third", Signal::HUP).err().expect("Expected an error");
+    assert_eq!(err.downcast_ref::<MembershipError>(),
+               Some(&MembershipError::UnknownMember("third".to_string())));
+    assert!(handle.signal("second", Signal::HUP).is_ok());
+    assert!(!rc2.recv().unwrap()); // Rendevous channels
+    assert!(rc1.recv().unwrap()); // Torn down with the error signal
+
+    match process.wait() {
+        Ok(_) => assert!(false, "Expected an error"),
+        Err(ProcessError::RunnerError(e)) => {
+            let err = e.downcast_ref::<GroupError>().expect("Expected a GroupError");
+            assert_eq!(err.failures().len(), 1);
+            let trigger = err.trigger().expect("Expected a trigger");
+            assert_eq!(trigger.name, Some("second".to_string()));
+            assert_eq!(trigger.index, 1);
+        },
+        Err(_) => assert!(false, "Wrong error type"),
+    }
+}
+
+#[test]
+fn test_launch_manual_signals() {
+    let (sn1, rc1) = chan::sync(0);
+    let runner1 = Box::new(TestRunner::new(1, sn1.clone())) as Box<Runner + Send>;
+    let (sn2, rc2) = chan::sync(0);
+    let runner2 = Box::new(TestRunner::new(2, sn2.clone())) as Box<Runner + Send>;
+
+    let composer = Composer::new(vec!(runner1, runner2), Signal::INT);
+    let manual = ManualSignals::new();
+    let source = MergedSignals::new().with(vec!(Signal::ALRM)).with(manual.clone());
+
+    let process = launch(composer, source);
+
+    assert!(process.ready().is_ok());
+
+    manual.raise(Signal::INT);
+    assert!(rc1.recv().unwrap()); // Rendevous channels
+    assert!(rc2.recv().unwrap());
+
+    assert!(process.wait().is_ok());
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum Message {
+    Os(Signal),
+    Reload,
+}
+
+impl From<Signal> for Message {
+    fn from(sig: Signal) -> Message {
+        Message::Os(sig)
+    }
+}
+
+// Counts reloads until told to stop by an OS signal.
+struct Reloader {
+    reloads: chan::Sender<usize>,
+}
+
+impl Runner<Message> for Reloader {
+    fn setup(&mut self) -> Result<(), MaridError> { Ok(()) }
+
+    fn run(self: Box<Self>, messages: Receiver<Message>) -> Result<(), MaridError> {
+        let mut count = 0;
+        while let Some(msg) = messages.recv() {
+            match msg {
+                Message::Reload => count += 1,
+                Message::Os(_) => break,
+            }
+        }
+        self.reloads.send(count);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_launch_messages() {
+    let (sn, rc) = chan::sync(2);
+    let runner1 = Box::new(Reloader { reloads: sn.clone() }) as Box<Runner<Message> + Send>;
+    let runner2 = Box::new(Reloader { reloads: sn }) as Box<Runner<Message> + Send>;
+
+    let composer = Composer::new(vec!(runner1, runner2), Message::Os(Signal::INT));
+    let process = launch(composer, vec!(Signal::INT));
+
+    assert!(process.ready().is_ok());
+
+    process.signal(Message::Reload);
+    process.signal(Message::Reload);
+    process.signal(Message::Os(Signal::INT));
+    assert!(process.wait().is_ok());
+
+    assert_eq!(rc.recv(), Some(2));
+    assert_eq!(rc.recv(), Some(2));
+}